@@ -1,27 +1,77 @@
 use dependency_graph::{DependencyGraph, Node, Step};
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use serde_json::{Map, Result, Value};
-use simplelog::warn;
+use simplelog::{info, warn};
+use std::collections::HashSet;
+use std::time::Instant;
 
 use crate::*;
 
 use filter::Executable;
 use probe::Runable;
+use trace::{BlockTrace, RawOutput, Trace};
 use wrapper::Wrapping;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Test {
     id: String,
+    #[serde(default)]
+    applicable_when: Option<String>,
+    /// Upstream values reported with the verdict, either a block id or a block id followed by
+    /// a JSON Pointer into its result, e.g. `sshd_config/0/source`.
+    #[serde(default)]
+    evidence: Vec<String>,
     blocks: Vec<Block>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// Verdict of a test, i.e. the result of its last block, with the evidence it was reached
+/// from: filter evidence under the filter block ids and the test's `evidence` entries.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Outcome {
+    /// `false` when the `applicable_when` block did not evaluate to `true`, the result is
+    /// `null` then.
+    pub applicable: bool,
+    pub result: Value,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub evidence: Map<String, Value>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum Block {
     Probe(ProbeBlock),
     Filter(FilterBlock),
 }
 
+// Picks the kind of block by its key, an untagged enum would hide why a block doesn't parse.
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let id = value
+            .get("id")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        if id.contains('/') {
+            return Err(de::Error::custom(format!(
+                "Block '{}': '/' is not allowed in a block id, it starts the pointer of an evidence entry!",
+                id
+            )));
+        }
+        let block = if value.get("probe").is_some() {
+            ProbeBlock::deserialize(value).map(Block::Probe)
+        } else if value.get("filter").is_some() {
+            FilterBlock::deserialize(value).map(Block::Filter)
+        } else {
+            return Err(de::Error::custom(format!(
+                "Block '{}': either 'probe' or 'filter' is expected!",
+                id
+            )));
+        };
+        block.map_err(|e| de::Error::custom(format!("Block '{}': {}", id, e)))
+    }
+}
+
 pub trait Entity {
     fn get_id(&self) -> &str;
 }
@@ -55,34 +105,263 @@ impl Test {
         }
         test.blocks = sorted_blocks;
         //dbg!(&test.blocks);
+        if let Some(condition) = &test.applicable_when {
+            if !test.blocks.iter().any(|b| b.get_id() == condition) {
+                return Err(serde::de::Error::custom(format!(
+                    "The 'applicable_when' block '{}' is not defined!",
+                    condition
+                )));
+            }
+        }
+        for entry in &test.evidence {
+            let (id, _) = split_evidence_entry(entry);
+            if !test.blocks.iter().any(|b| b.get_id() == id) {
+                return Err(serde::de::Error::custom(format!(
+                    "The 'evidence' block '{}' is not defined!",
+                    id
+                )));
+            }
+        }
+        for block in &test.blocks {
+            block.compile().map_err(serde::de::Error::custom)?;
+        }
         Result::Ok(test)
     }
 
-    pub fn execute(&self, runner: &mut Runner) -> Value {
+    /// Ids of the block and all the blocks it transitively depends on.
+    fn dependency_closure(&self, id: &str) -> HashSet<String> {
+        let mut closure = HashSet::new();
+        let mut pending = vec![id.to_string()];
+        while let Some(id) = pending.pop() {
+            if closure.insert(id.clone()) {
+                if let Some(block) = self.blocks.iter().find(|b| b.get_id() == id) {
+                    pending.extend(block.dependencies().iter().cloned());
+                }
+            }
+        }
+        closure
+    }
+
+    pub fn execute(&self, runner: &mut Runner) -> error::Result<Outcome> {
+        self.run(runner, None)
+    }
+
+    /// Executes the test recording every executed block into the trace.
+    pub fn execute_with_trace(
+        &self,
+        runner: &mut Runner,
+        trace: &mut Trace,
+    ) -> error::Result<Outcome> {
+        runner.set_tracing(true);
+        let outcome = self.run(runner, Some(&mut trace.blocks));
+        runner.set_tracing(false);
+        outcome
+    }
+
+    fn run(
+        &self,
+        runner: &mut Runner,
+        mut trace: Option<&mut Vec<BlockTrace>>,
+    ) -> error::Result<Outcome> {
         let mut results: Map<String, Value> = Map::new();
-        let mut result = Value::Null;
+        let mut evidence: Map<String, Value> = Map::new();
+        // Blocks are sorted already, so the condition's subset can run ahead of the rest.
+        let condition_blocks = match &self.applicable_when {
+            Some(condition) => {
+                let closure = self.dependency_closure(condition);
+                for block in self.blocks.iter().filter(|b| closure.contains(b.get_id())) {
+                    block.execute_with_evidence(
+                        runner,
+                        &mut results,
+                        &mut evidence,
+                        trace.as_deref_mut(),
+                    )?;
+                }
+                if results.get(condition) != Some(&Value::Bool(true)) {
+                    info!("Test '{}' is not applicable.", self.id);
+                    debug!("Results: {:#?}", &results);
+                    return Ok(Outcome {
+                        applicable: false,
+                        result: Value::Null,
+                        evidence: self.capture_evidence(&results, evidence),
+                    });
+                }
+                closure
+            }
+            None => HashSet::new(),
+        };
         for block in &self.blocks {
-            result = block.execute(runner, &results);
-            results.insert(block.get_id().to_string(), result.clone());
+            if condition_blocks.contains(block.get_id()) {
+                continue;
+            }
+            block.execute_with_evidence(
+                runner,
+                &mut results,
+                &mut evidence,
+                trace.as_deref_mut(),
+            )?;
         }
         debug!("Results: {:#?}", &results);
-        result.into()
+        // The condition's blocks may sort last, the result is the last block outside of them.
+        let result_block = self
+            .blocks
+            .iter()
+            .rev()
+            .find(|b| !condition_blocks.contains(b.get_id()))
+            .or(self.blocks.last());
+        Ok(Outcome {
+            applicable: true,
+            result: result_block
+                .and_then(|b| results.get(b.get_id()))
+                .cloned()
+                .unwrap_or(Value::Null),
+            evidence: self.capture_evidence(&results, evidence),
+        })
+    }
+
+    /// Adds the values selected by the test's `evidence` entries, skipping the ones which
+    /// were not computed (e.g. for a not applicable test).
+    fn capture_evidence(
+        &self,
+        results: &Map<String, Value>,
+        mut evidence: Map<String, Value>,
+    ) -> Map<String, Value> {
+        for entry in &self.evidence {
+            let (id, pointer) = split_evidence_entry(entry);
+            if let Some(value) = results.get(id) {
+                evidence.insert(
+                    entry.clone(),
+                    value.pointer(pointer).cloned().unwrap_or(Value::Null),
+                );
+            }
+        }
+        evidence
+    }
+}
+
+/// Splits `block_id/json/pointer` into the block id and the (possibly empty) pointer.
+fn split_evidence_entry(entry: &str) -> (&str, &str) {
+    match entry.find('/') {
+        Some(i) => entry.split_at(i),
+        None => (entry, ""),
     }
 }
 
 impl Block {
     pub fn create_from_json(json_string: String) -> Result<Self> {
-        serde_json::from_str(&json_string)
+        let block: Self = serde_json::from_str(&json_string)?;
+        block.compile().map_err(serde::de::Error::custom)?;
+        Ok(block)
     }
 
-    pub fn execute(&self, runner: &mut Runner, results: &Map<String, Value>) -> Value {
+    /// Compiles the block's expressions, so that syntax errors are reported at load time.
+    pub fn compile(&self) -> error::Result<()> {
         match self {
+            Block::Probe(block) => block.wrapper.compile(),
+            Block::Filter(block) => block
+                .filter
+                .compile()
+                .and_then(|_| block.check_references()),
+        }
+        .map_err(|e| error::Error::Block {
+            id: self.get_id().to_string(),
+            error: Box::new(e),
+        })
+    }
+
+    pub fn execute(
+        &self,
+        runner: &mut Runner,
+        results: &Map<String, Value>,
+    ) -> error::Result<Value> {
+        self.execute_with_trace(runner, results, None)
+    }
+
+    /// Executes the block, appending a record of the execution to the trace if any.
+    pub fn execute_with_trace(
+        &self,
+        runner: &mut Runner,
+        results: &Map<String, Value>,
+        trace: Option<&mut Vec<BlockTrace>>,
+    ) -> error::Result<Value> {
+        let start = Instant::now();
+        let mut block_trace = trace.as_ref().map(|_| {
+            // Drop the commands of whatever ran before the block.
+            runner.take_trace();
+            BlockTrace {
+                id: self.get_id().to_string(),
+                ..Default::default()
+            }
+        });
+        let result = match self {
             Block::Probe(block) => {
                 let output = block.probe.run(runner);
-                block.wrapper.wrap_all(&output)
+                if let Some(block_trace) = &mut block_trace {
+                    block_trace.raw = output.iter().map(|(s, o)| RawOutput::new(s, o)).collect();
+                }
+                block.wrapper.wrap_all_with_runner(runner, &output)
+            }
+            Block::Filter(block) => {
+                // Only the declared sources, which the dependency graph runs before the filter.
+                let sources: Map<String, Value> = results
+                    .iter()
+                    .filter(|(id, _)| block.src.contains(id))
+                    .map(|(id, value)| (id.clone(), value.clone()))
+                    .collect();
+                let result = block.filter.execute(runner, &sources);
+                if let Some(block_trace) = &mut block_trace {
+                    block_trace.inputs = Some(sources);
+                }
+                result
+            }
+        }
+        .map_err(|e| error::Error::Block {
+            id: self.get_id().to_string(),
+            error: Box::new(e),
+        });
+        if let (Some(trace), Some(mut block_trace)) = (trace, block_trace) {
+            block_trace.commands = runner.take_trace();
+            match &result {
+                Ok(value) => block_trace.result = Some(value.clone()),
+                Err(e) => block_trace.error = Some(e.to_string()),
+            }
+            block_trace.wall_time_ms = start.elapsed().as_secs_f64() * 1000.0;
+            trace.push(block_trace);
+        }
+        result
+    }
+
+    /// Executes the block and stores its result, splitting off the evidence of filters
+    /// declaring it.
+    fn execute_with_evidence(
+        &self,
+        runner: &mut Runner,
+        results: &mut Map<String, Value>,
+        evidence: &mut Map<String, Value>,
+        trace: Option<&mut Vec<BlockTrace>>,
+    ) -> error::Result<()> {
+        let mut result = self.execute_with_trace(runner, results, trace)?;
+        if let Block::Filter(block) = self {
+            if block.evidence {
+                let (value, block_evidence) =
+                    block
+                        .split_evidence(result)
+                        .map_err(|e| error::Error::Block {
+                            id: block.id.clone(),
+                            error: Box::new(e),
+                        })?;
+                evidence.insert(block.id.clone(), block_evidence);
+                result = value;
             }
-            Block::Filter(block) => block.filter.execute(runner, results),
         }
+        results.insert(self.get_id().to_string(), result);
+        Ok(())
+    }
+}
+
+impl Entity for Test {
+    fn get_id(&self) -> &str {
+        self.id.as_str()
     }
 }
 
@@ -120,7 +399,7 @@ pub struct ProbeBlock {
     src: Vec<String>,
     probe: probe::Probe,
     #[serde(default)]
-    wrapper: wrapper::Wrapper,
+    wrapper: wrapper::Pipeline,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -129,6 +408,51 @@ pub struct FilterBlock {
     #[serde(default)]
     src: Vec<String>,
     filter: filter::Filter,
+    /// The filter returns `{"result": ..., "evidence": ...}`, the result being the block's
+    /// value and the evidence (e.g. the violating items) being reported with the verdict.
+    #[serde(default)]
+    evidence: bool,
+}
+
+impl FilterBlock {
+    fn split_evidence(&self, value: Value) -> error::Result<(Value, Value)> {
+        match value {
+            Value::Object(mut map) if map.contains_key("result") => Ok((
+                map.remove("result").unwrap_or_default(),
+                map.remove("evidence").unwrap_or_default(),
+            )),
+            value => Err(error::Error::Filter(format!(
+                "An object with 'result' and 'evidence' is expected, got '{}'!",
+                value
+            ))),
+        }
+    }
+
+    /// Checks the block results read by the filter against `src`, unknown ones are errors,
+    /// unused sources are only reported.
+    fn check_references(&self) -> error::Result<()> {
+        let Some(references) = self.filter.references() else {
+            return Ok(());
+        };
+        for src in &self.src {
+            if !references.contains(src) {
+                warn!("Block '{}': the source '{}' is not used!", self.id, src);
+            }
+        }
+        let unknown: Vec<String> = references
+            .iter()
+            .filter(|name| !self.src.contains(name))
+            .map(|name| format!("'{}'", name))
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(error::Error::Filter(format!(
+                "Unknown identifier(s) {}, neither 'args' nor listed in 'src'!",
+                unknown.join(", ")
+            )))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -169,6 +493,8 @@ mod tests {
             res.expect("Oops!"),
             Test {
                 id: "test_id".to_string(),
+                applicable_when: None,
+                evidence: Vec::new(),
                 blocks: vec![
                     Block::Probe(ProbeBlock {
                         id: "block_1_id".to_string(),
@@ -177,7 +503,7 @@ mod tests {
                             exec: "echo".to_string(),
                             args: vec!["{\"result\": true}".to_string()],
                         }),
-                        wrapper: wrapper::Wrapper::default(),
+                        wrapper: wrapper::Pipeline::default(),
                     }),
                     Block::Filter(FilterBlock {
                         id: "block_2_id".to_string(),
@@ -185,7 +511,9 @@ mod tests {
                         filter: filter::Filter::CEL(filter::cel::CELFilter {
                             expr: "1 + 1".to_string(),
                             args: None,
-                        })
+                            program: Default::default(),
+                        }),
+                        evidence: false,
                     }),
                 ]
             }
@@ -214,7 +542,9 @@ mod tests {
                 filter: filter::Filter::CEL(filter::cel::CELFilter {
                     expr: "1 + 1".to_string(),
                     args: None,
-                })
+                    program: Default::default(),
+                }),
+                evidence: false,
             })
         )
     }
@@ -236,7 +566,7 @@ mod tests {
 
         let mut r = Runner::new();
         let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
-        let s = b.execute(&mut r, &Map::new());
+        let s = b.execute(&mut r, &Map::new()).unwrap();
         println!("{:#?}", s);
     }
 
@@ -260,7 +590,7 @@ mod tests {
 
         let mut r = Runner::new();
         let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
-        let s = b.execute(&mut r, &Map::new());
+        let s = b.execute(&mut r, &Map::new()).unwrap();
         println!("{:#?}", s);
     }
 
@@ -279,10 +609,231 @@ mod tests {
 
         let mut r = Runner::new();
         let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
-        let s = b.execute(&mut r, &Map::new());
+        let s = b.execute(&mut r, &Map::new()).unwrap();
         println!("{:#?}", s);
     }
 
+    #[test]
+    fn block_create_from_json_and_run_probe_process_yaml_error() {
+        let json_s = r#"{
+            "id": "block_id",
+            "probe": {
+                "process": {
+                    "exec": "echo",
+                    "args": ["key: [unclosed"]
+                }
+            },
+            "wrapper": {
+                "yaml": {}
+            }
+        }"#
+        .to_string();
+
+        let mut r = Runner::new();
+        let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
+        let e = b.execute(&mut r, &Map::new()).unwrap_err();
+        println!("{}", e);
+        assert!(matches!(e, error::Error::Block { id, .. } if id == "block_id"));
+    }
+
+    #[test]
+    fn test_create_from_json_compile_errors() {
+        let json_s = r#"{
+            "id": "test_id",
+            "blocks": [
+                {
+                    "id": "block_1_id",
+                    "probe": {
+                        "file": {
+                            "paths": ["/etc/fstab"]
+                        }
+                    },
+                    "wrapper": {
+                        "regexp": {
+                            "expr": "^(?<fs_spec>\\S+"
+                        }
+                    }
+                }
+            ]
+        }"#;
+
+        let e = Test::create_from_json(json_s.to_string()).unwrap_err();
+        println!("{}", e);
+        assert!(e.to_string().contains("Block 'block_1_id': Wrapper error"));
+
+        let json_s = r#"{
+            "id": "block_2_id",
+            "filter": {
+                "cel": {
+                    "expr": "1 +",
+                    "args": null
+                }
+            }
+        }"#;
+        let e = Block::create_from_json(json_s.to_string()).unwrap_err();
+        println!("{}", e);
+        assert!(e.to_string().contains("Block 'block_2_id': Filter error"));
+    }
+
+    #[test]
+    fn test_create_from_json_pipeline_errors() {
+        let block = |wrapper: &str| {
+            format!(
+                r#"{{"id": "block_id", "probe": {{"file": {{"paths": ["/etc/fstab"]}}}}, "wrapper": {}}}"#,
+                wrapper
+            )
+        };
+
+        let e = Block::create_from_json(block("[]")).unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("Block 'block_id': A wrapper pipeline needs at least one stage!"));
+
+        let e =
+            Block::create_from_json(block(r#"[{"raw-lines": {}}, {"colums": {}}]"#)).unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("Block 'block_id': Wrapper stage 2: unknown variant `colums`"));
+
+        let e =
+            Block::create_from_json(r#"{"id": "block_id", "prob": {}}"#.to_string()).unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("Block 'block_id': either 'probe' or 'filter' is expected!"));
+    }
+
+    #[test]
+    fn test_create_from_json_unknown_identifier() {
+        let json_s = r#"{
+            "id": "test_id",
+            "blocks": [
+                {
+                    "id": "block_1_id",
+                    "probe": {
+                        "process": {
+                            "exec": "echo",
+                            "args": ["{\"result\": true}"]
+                        }
+                    }
+                },
+                {
+                    "id": "block_2_id",
+                    "src": ["block_1_id"],
+                    "filter": {
+                        "cel": {
+                            "expr": "block_1_id[0].data.result && blok_1_id[0].data.result",
+                            "args": null
+                        }
+                    }
+                }
+            ]
+        }"#;
+
+        let e = Test::create_from_json(json_s.to_string()).unwrap_err();
+        println!("{}", e);
+        assert!(e.to_string().contains(
+            "Block 'block_2_id': Filter error: Unknown identifier(s) 'blok_1_id', neither"
+        ));
+
+        // Type names are not block results.
+        let json_s = json_s.replace(
+            "block_1_id[0].data.result && blok_1_id[0].data.result",
+            "type(block_1_id) == list && type(args) != map && int(1) == 1",
+        );
+        Test::create_from_json(json_s).unwrap();
+    }
+
+    #[cfg(feature = "rego")]
+    #[test]
+    fn test_filter_sees_only_its_sources() {
+        let json_s = r#"{
+            "id": "test_id",
+            "blocks": [
+                {
+                    "id": "block_1_id",
+                    "probe": {"process": {"exec": "echo", "args": ["1"]}}
+                },
+                {
+                    "id": "block_2_id",
+                    "probe": {"process": {"exec": "echo", "args": ["2"]}}
+                },
+                {
+                    "id": "result",
+                    "src": ["block_1_id"],
+                    "filter": {
+                        "rego": {"expr": "[input.block_1_id[0].data, input.block_2_id]"}
+                    }
+                }
+            ]
+        }"#;
+
+        let t = Test::create_from_json(json_s.to_string()).unwrap();
+        let outcome = t.execute(&mut Runner::new()).unwrap();
+        // The undeclared block is undefined, whichever order the probes ran in.
+        assert_eq!(outcome.result, Value::Null);
+
+        let t = Test::create_from_json(json_s.replace(", input.block_2_id", "")).unwrap();
+        let outcome = t.execute(&mut Runner::new()).unwrap();
+        assert_eq!(outcome.result, serde_json::json!([1]));
+    }
+
+    #[test]
+    fn test_execute_with_evidence() {
+        let json_s = r#"{
+            "id": "test_id",
+            "evidence": ["block_1_id/0/source", "block_1_id/0/data/missing"],
+            "blocks": [
+                {
+                    "id": "block_1_id",
+                    "probe": {
+                        "process": {
+                            "exec": "echo",
+                            "args": ["[{\"name\": \"a\", \"uid\": 0}, {\"name\": \"b\", \"uid\": 1}]"]
+                        }
+                    }
+                },
+                {
+                    "id": "block_2_id",
+                    "src": ["block_1_id"],
+                    "filter": {
+                        "cel": {
+                            "expr": "{'result': block_1_id[0].data.all(u, u.uid != 0), 'evidence': block_1_id[0].data.filter(u, u.uid == 0)}"
+                        }
+                    },
+                    "evidence": true
+                }
+            ]
+        }"#;
+
+        let t = Test::create_from_json(json_s.to_string()).unwrap();
+        let outcome = t.execute(&mut Runner::new()).unwrap();
+        assert_eq!(outcome.result, false);
+        assert_eq!(
+            Value::from(outcome.evidence),
+            serde_json::json!({
+                "block_2_id": [{"name": "a", "uid": 0}],
+                "block_1_id/0/source": "echo",
+                "block_1_id/0/data/missing": null
+            })
+        );
+
+        let e =
+            Test::create_from_json(json_s.replace("\"block_1_id/0/source\", ", "\"block_3_id\", "))
+                .unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("The 'evidence' block 'block_3_id' is not defined!"));
+
+        let e = Test::create_from_json(json_s.replace("block_1_id", "block/1")).unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("Block 'block/1': '/' is not allowed in a block id"));
+
+        let t = Test::create_from_json(json_s.replace("{'result': ", "{'verdict': ")).unwrap();
+        let e = t.execute(&mut Runner::new()).unwrap_err();
+        assert!(matches!(e, error::Error::Block { id, .. } if id == "block_2_id"));
+    }
+
     #[test]
     fn runner_unglob() {
         let mut r = Runner::new();
@@ -0,0 +1,41 @@
+use std::cell::OnceCell;
+use std::fmt;
+
+use crate::error::Result;
+
+/// Artifact built from a block definition (a CEL program, a regex), compiled once when the
+/// test is loaded and reused by every execution. It is not (de)serialized and does not take
+/// part in comparisons of the definitions.
+pub struct Compiled<T>(OnceCell<T>);
+
+impl<T> Compiled<T> {
+    pub fn get_or_try_init(&self, init: impl FnOnce() -> Result<T>) -> Result<&T> {
+        if let Some(value) = self.0.get() {
+            return Ok(value);
+        }
+        let value = init()?;
+        Ok(self.0.get_or_init(|| value))
+    }
+}
+
+impl<T> Default for Compiled<T> {
+    fn default() -> Self {
+        Compiled(OnceCell::new())
+    }
+}
+
+impl<T> PartialEq for Compiled<T> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> fmt::Debug for Compiled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.get().is_some() {
+            write!(f, "Compiled")
+        } else {
+            write!(f, "NotCompiled")
+        }
+    }
+}
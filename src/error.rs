@@ -0,0 +1,22 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Wrapper(String),
+    Filter(String),
+    Block { id: String, error: Box<Error> },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Wrapper(msg) => write!(f, "Wrapper error: {}", msg),
+            Error::Filter(msg) => write!(f, "Filter error: {}", msg),
+            Error::Block { id, error } => write!(f, "Block '{}': {}", id, error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
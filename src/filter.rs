@@ -2,14 +2,23 @@ use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
 pub mod cel;
+#[cfg(feature = "jq")]
+pub mod jq;
 #[cfg(feature = "rego")]
 pub mod rego;
+#[cfg(feature = "rhai")]
+pub mod rhai;
 
+use crate::error::Result;
 use crate::runner::Runner;
 
 use crate::filter::cel::CELFilter;
+#[cfg(feature = "jq")]
+use crate::filter::jq::JQFilter;
 #[cfg(feature = "rego")]
 use crate::filter::rego::REGOFilter;
+#[cfg(feature = "rhai")]
+use crate::filter::rhai::RHAIFilter;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -17,18 +26,70 @@ pub enum Filter {
     CEL(CELFilter),
     #[cfg(feature = "rego")]
     REGO(REGOFilter),
+    #[cfg(feature = "rhai")]
+    RHAI(RHAIFilter),
+    #[cfg(feature = "jq")]
+    JQ(JQFilter),
+}
+
+/// 1-based line and column of a byte offset in a filter expression, for error messages.
+#[cfg(feature = "jq")]
+pub(crate) fn line_column(code: &str, offset: usize) -> (usize, usize) {
+    let line = code[..offset].matches('\n').count() + 1;
+    let column = code[..offset]
+        .rsplit('\n')
+        .next()
+        .map_or(0, |l| l.chars().count())
+        + 1;
+    (line, column)
 }
 
 pub trait Executable {
-    fn execute(&self, runner: &mut Runner, sources: &Map<String, Value>) -> Value;
+    /// Builds whatever the filter needs ahead of the first execution, reporting syntax errors.
+    fn compile(&self) -> Result<()> {
+        Ok(())
+    }
+    /// Block results the filter reads, `None` when they can't be determined statically.
+    fn references(&self) -> Option<Vec<String>> {
+        None
+    }
+    fn execute(&self, runner: &mut Runner, sources: &Map<String, Value>) -> Result<Value>;
 }
 
 impl Executable for Filter {
-    fn execute(&self, runner: &mut Runner, sources: &Map<String, Value>) -> Value {
+    fn compile(&self) -> Result<()> {
+        match self {
+            Filter::CEL(cel_filter) => cel_filter.compile(),
+            #[cfg(feature = "rego")]
+            Filter::REGO(rego_filter) => rego_filter.compile(),
+            #[cfg(feature = "rhai")]
+            Filter::RHAI(rhai_filter) => rhai_filter.compile(),
+            #[cfg(feature = "jq")]
+            Filter::JQ(jq_filter) => jq_filter.compile(),
+        }
+    }
+
+    fn references(&self) -> Option<Vec<String>> {
+        match self {
+            Filter::CEL(cel_filter) => cel_filter.references(),
+            #[cfg(feature = "rego")]
+            Filter::REGO(rego_filter) => rego_filter.references(),
+            #[cfg(feature = "rhai")]
+            Filter::RHAI(rhai_filter) => rhai_filter.references(),
+            #[cfg(feature = "jq")]
+            Filter::JQ(jq_filter) => jq_filter.references(),
+        }
+    }
+
+    fn execute(&self, runner: &mut Runner, sources: &Map<String, Value>) -> Result<Value> {
         match self {
             Filter::CEL(cel_filter) => cel_filter.execute(runner, sources),
             #[cfg(feature = "rego")]
             Filter::REGO(rego_filter) => rego_filter.execute(runner, sources),
+            #[cfg(feature = "rhai")]
+            Filter::RHAI(rhai_filter) => rhai_filter.execute(runner, sources),
+            #[cfg(feature = "jq")]
+            Filter::JQ(jq_filter) => jq_filter.execute(runner, sources),
         }
     }
 }
@@ -1,9 +1,18 @@
-use cel_interpreter::extractors::This;
-use cel_interpreter::{Context, ExecutionError, FunctionContext, Program};
+use cel_interpreter::extractors::{Identifier, This};
+use cel_interpreter::{Context, ExecutionError, Expression, FunctionContext, Program};
+use cel_parser::{Atom, Member};
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
+use crate::compiled::Compiled;
+use crate::error::Error;
 use crate::filter::Executable;
 use crate::runner::Runner;
 
@@ -13,21 +22,246 @@ type Result<T> = std::result::Result<T, ExecutionError>;
 pub struct CELFilter {
     pub expr: String,
     pub args: Option<Map<String, Value>>,
+    #[serde(skip)]
+    pub program: Compiled<CELProgram>,
+}
+
+/// A compiled filter: the program and the regexes of the literal patterns passed to `matches`,
+/// `captures` and `glob_matches`, keyed by their source.
+pub struct CELProgram {
+    program: Program,
+    regexes: Arc<HashMap<String, regex::Regex>>,
+}
+
+impl CELFilter {
+    fn program(&self) -> crate::error::Result<&CELProgram> {
+        self.program.get_or_try_init(|| {
+            let error = |e: String| Error::Filter(format!("Can't compile '{}': {}", self.expr, e));
+            let program = Program::compile(&self.expr).map_err(|e| error(e.to_string()))?;
+            let expression = cel_parser::parse(&self.expr).map_err(|e| error(e.to_string()))?;
+            let mut patterns = Vec::new();
+            literal_patterns(&expression, &mut patterns);
+            let mut regexes = HashMap::new();
+            for pattern in patterns {
+                let regex = regex::Regex::new(&pattern)
+                    .map_err(|e| error(format!("'{}' not a valid regex: {}", pattern, e)))?;
+                regexes.insert(pattern, regex);
+            }
+            Ok(CELProgram {
+                program,
+                regexes: Arc::new(regexes),
+            })
+        })
+    }
 }
 
 impl Executable for CELFilter {
-    fn execute(&self, _: &mut Runner, sources: &Map<String, Value>) -> Value {
-        let program = Program::compile(&self.expr).unwrap();
+    fn compile(&self) -> crate::error::Result<()> {
+        self.program().map(|_| ())
+    }
+
+    fn references(&self) -> Option<Vec<String>> {
+        let expression = cel_parser::parse(&self.expr).ok()?;
+        let mut names = Vec::new();
+        free_variables(&expression, &mut Vec::new(), &mut names);
+        Some(
+            names
+                .into_iter()
+                .filter(|name| name.as_str() != "args" && !TYPES.contains(&name.as_str()))
+                .map(|name| name.to_string())
+                .collect(),
+        )
+    }
+
+    fn execute(&self, _: &mut Runner, sources: &Map<String, Value>) -> crate::error::Result<Value> {
+        let CELProgram { program, regexes } = self.program()?;
         let mut context = Context::default();
-        context.add_variable("args", &self.args).unwrap();
+        context
+            .add_variable("args", &self.args)
+            .map_err(|e| Error::Filter(e.to_string()))?;
         for kv in sources.iter() {
-            context.add_variable(kv.0, &kv.1).unwrap();
+            context
+                .add_variable(kv.0, kv.1)
+                .map_err(|e| Error::Filter(e.to_string()))?;
         }
 
         context.add_function("has_value_of", has_value_of);
-        let value = program.execute(&context).unwrap();
-        //println!("{:?}", value);
-        value.json().expect("CEL -> SERDE error!")
+        let r = regexes.clone();
+        context.add_function(
+            "matches",
+            move |ftx: &FunctionContext, this: This<Arc<String>>, expr: Arc<String>| {
+                matches(ftx, &r, this, expr)
+            },
+        );
+        let r = regexes.clone();
+        context.add_function(
+            "captures",
+            move |ftx: &FunctionContext, this: This<Arc<String>>, expr: Arc<String>| {
+                captures(ftx, &r, this, expr)
+            },
+        );
+        context.add_function("rpm_compare", rpm_compare);
+        context.add_function("deb_compare", deb_compare);
+        context.add_function("semver_matches", semver_matches);
+        context.add_function("in_cidr", in_cidr);
+        context.add_function("perm_at_most", perm_at_most);
+        let r = regexes.clone();
+        context.add_function(
+            "glob_matches",
+            move |ftx: &FunctionContext, this: This<Arc<String>>, pattern: Arc<String>| {
+                glob_matches(ftx, &r, this, pattern)
+            },
+        );
+        context.add_function("all_values", all_values);
+        context.add_function("any_values", any_values);
+        let value = program
+            .execute(&context)
+            .map_err(|e| Error::Filter(e.to_string()))?;
+        value.json().map_err(|e| Error::Filter(e.to_string()))
+    }
+}
+
+/// Identifiers of the built-in types, e.g. `type(x) == int`.
+const TYPES: [&str; 10] = [
+    "int",
+    "uint",
+    "double",
+    "bool",
+    "string",
+    "bytes",
+    "list",
+    "map",
+    "null_type",
+    "type",
+];
+
+/// Functions whose first argument names a variable bound in the remaining ones.
+const MACROS: [&str; 7] = [
+    "all",
+    "exists",
+    "exists_one",
+    "map",
+    "filter",
+    "all_values",
+    "any_values",
+];
+
+/// Collects the identifiers which are not bound by an enclosing macro, in order of appearance.
+fn free_variables(
+    expression: &Expression,
+    bound: &mut Vec<Arc<String>>,
+    names: &mut Vec<Arc<String>>,
+) {
+    match expression {
+        Expression::Arithmetic(e1, _, e2)
+        | Expression::Relation(e1, _, e2)
+        | Expression::Or(e1, e2)
+        | Expression::And(e1, e2) => {
+            free_variables(e1, bound, names);
+            free_variables(e2, bound, names);
+        }
+        Expression::Ternary(e1, e2, e3) => {
+            free_variables(e1, bound, names);
+            free_variables(e2, bound, names);
+            free_variables(e3, bound, names);
+        }
+        Expression::Unary(_, e) => free_variables(e, bound, names),
+        Expression::Member(e, member) => {
+            free_variables(e, bound, names);
+            match &**member {
+                Member::Index(index) => free_variables(index, bound, names),
+                Member::Fields(fields) => {
+                    for (_, e) in fields {
+                        free_variables(e, bound, names);
+                    }
+                }
+                Member::Attribute(_) => {}
+            }
+        }
+        Expression::FunctionCall(function, target, args) => {
+            if let Some(target) = target {
+                free_variables(target, bound, names);
+            }
+            let variable = match (&**function, args.first()) {
+                (Expression::Ident(f), Some(Expression::Ident(v)))
+                    if MACROS.contains(&f.as_str()) && args.len() > 1 =>
+                {
+                    Some(v.clone())
+                }
+                _ => None,
+            };
+            match variable {
+                Some(v) => {
+                    bound.push(v);
+                    for e in &args[1..] {
+                        free_variables(e, bound, names);
+                    }
+                    bound.pop();
+                }
+                None => {
+                    for e in args {
+                        free_variables(e, bound, names);
+                    }
+                }
+            }
+        }
+        Expression::List(items) => {
+            for e in items {
+                free_variables(e, bound, names);
+            }
+        }
+        Expression::Map(entries) => {
+            for (k, v) in entries {
+                free_variables(k, bound, names);
+                free_variables(v, bound, names);
+            }
+        }
+        Expression::Atom(_) => {}
+        Expression::Ident(name) => {
+            if !bound.contains(name) && !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+}
+
+/// Collects the regexes given as string literals to `matches`, `captures` and `glob_matches`.
+fn literal_patterns(expression: &Expression, patterns: &mut Vec<String>) {
+    let mut children: Vec<&Expression> = Vec::new();
+    match expression {
+        Expression::Arithmetic(e1, _, e2)
+        | Expression::Relation(e1, _, e2)
+        | Expression::Or(e1, e2)
+        | Expression::And(e1, e2) => children.extend([&**e1, &**e2]),
+        Expression::Ternary(e1, e2, e3) => children.extend([&**e1, &**e2, &**e3]),
+        Expression::Unary(_, e) => children.push(e),
+        Expression::Member(e, member) => {
+            children.push(e);
+            match &**member {
+                Member::Index(index) => children.push(index),
+                Member::Fields(fields) => children.extend(fields.iter().map(|(_, e)| e)),
+                Member::Attribute(_) => {}
+            }
+        }
+        Expression::FunctionCall(function, target, args) => {
+            if let (Expression::Ident(f), Some(Expression::Atom(Atom::String(s)))) =
+                (&**function, args.first())
+            {
+                match f.as_str() {
+                    "matches" | "captures" => patterns.push(s.to_string()),
+                    "glob_matches" => patterns.push(glob_regex(s)),
+                    _ => {}
+                }
+            }
+            children.extend(target.as_deref());
+            children.extend(args);
+        }
+        Expression::List(items) => children.extend(items),
+        Expression::Map(entries) => children.extend(entries.iter().flat_map(|(k, v)| [k, v])),
+        Expression::Atom(_) | Expression::Ident(_) => {}
+    }
+    for e in children {
+        literal_patterns(e, patterns);
     }
 }
 
@@ -59,6 +293,11 @@ fn has_value_of(
                             false
                         }
                     }
+                    // Typed values, e.g. produced by wrapper type coercion.
+                    cel_interpreter::Value::List(l) => l.contains(&value),
+                    cel_interpreter::Value::Bool(_)
+                    | cel_interpreter::Value::UInt(_)
+                    | cel_interpreter::Value::Float(_) => *v == value,
                     // TODO: Error!
                     _ => false,
                 }
@@ -71,6 +310,384 @@ fn has_value_of(
     Ok(result)
 }
 
+/// The regex compiled with the program for a literal pattern, a new one for a computed pattern.
+fn regex<'a>(
+    ftx: &FunctionContext,
+    regexes: &'a HashMap<String, regex::Regex>,
+    expr: &str,
+) -> Result<Cow<'a, regex::Regex>> {
+    match regexes.get(expr) {
+        Some(regex) => Ok(Cow::Borrowed(regex)),
+        None => regex::Regex::new(expr)
+            .map(Cow::Owned)
+            .map_err(|e| ftx.error(format!("'{}' not a valid regex: {}", expr, e))),
+    }
+}
+
+/// `'abc'.matches('^a')`
+fn matches(
+    ftx: &FunctionContext,
+    regexes: &HashMap<String, regex::Regex>,
+    This(this): This<Arc<String>>,
+    expr: Arc<String>,
+) -> Result<bool> {
+    Ok(regex(ftx, regexes, &expr)?.is_match(&this))
+}
+
+/// `'PermitRootLogin no'.captures('^(\\w+) (\\w+)$')[2] == 'no'`
+///
+/// Returns the whole match followed by every group (empty when a group did not participate),
+/// or an empty list when the string does not match.
+fn captures(
+    ftx: &FunctionContext,
+    regexes: &HashMap<String, regex::Regex>,
+    This(this): This<Arc<String>>,
+    expr: Arc<String>,
+) -> Result<cel_interpreter::Value> {
+    let groups: Vec<cel_interpreter::Value> = match regex(ftx, regexes, &expr)?.captures(&this) {
+        Some(c) => c
+            .iter()
+            .map(|m| m.map_or("", |m| m.as_str()).to_string().into())
+            .collect(),
+        None => Vec::new(),
+    };
+    Ok(groups.into())
+}
+
+fn ordering(o: Ordering) -> i64 {
+    match o {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+
+/// Splits `[epoch:]version[-release]`, the epoch defaulting to 0.
+fn split_evr(evr: &str) -> (u64, &str, Option<&str>) {
+    let (epoch, rest) = match evr.split_once(':') {
+        Some((e, rest)) if e.chars().all(|c| c.is_ascii_digit()) => (e.parse().unwrap_or(0), rest),
+        _ => (0, evr),
+    };
+    match rest.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, Some(release)),
+        None => (epoch, rest, None),
+    }
+}
+
+fn cmp_numeric(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// rpmvercmp(), including the `~` (sorts before anything) and `^` (sorts after the end of
+/// the string, before anything else) separators.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    let separator = |c: char| !c.is_ascii_alphanumeric() && c != '~' && c != '^';
+    let (mut a, mut b) = (a, b);
+    loop {
+        a = a.trim_start_matches(separator);
+        b = b.trim_start_matches(separator);
+
+        if a.starts_with('~') || b.starts_with('~') {
+            if !a.starts_with('~') {
+                return Ordering::Greater;
+            }
+            if !b.starts_with('~') {
+                return Ordering::Less;
+            }
+            a = &a[1..];
+            b = &b[1..];
+            continue;
+        }
+        if a.starts_with('^') || b.starts_with('^') {
+            if a.is_empty() {
+                return Ordering::Less;
+            }
+            if b.is_empty() {
+                return Ordering::Greater;
+            }
+            if !a.starts_with('^') {
+                return Ordering::Greater;
+            }
+            if !b.starts_with('^') {
+                return Ordering::Less;
+            }
+            a = &a[1..];
+            b = &b[1..];
+            continue;
+        }
+        if a.is_empty() || b.is_empty() {
+            break;
+        }
+
+        let numeric = a.starts_with(|c: char| c.is_ascii_digit());
+        let segment = |s: &str| -> usize {
+            s.find(|c: char| {
+                if numeric {
+                    !c.is_ascii_digit()
+                } else {
+                    !c.is_ascii_alphabetic()
+                }
+            })
+            .unwrap_or(s.len())
+        };
+        let (la, lb) = (segment(a), segment(b));
+        if lb == 0 {
+            // Numeric segments are newer than alphabetic ones.
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+        let o = if numeric {
+            cmp_numeric(&a[..la], &b[..lb])
+        } else {
+            a[..la].cmp(&b[..lb])
+        };
+        if o != Ordering::Equal {
+            return o;
+        }
+        a = &a[la..];
+        b = &b[lb..];
+    }
+    a.len().cmp(&b.len())
+}
+
+/// `'1:2.0-1.el9'.rpm_compare('2.0-2.el9') > 0`
+fn rpm_compare(This(this): This<Arc<String>>, other: Arc<String>) -> i64 {
+    let (ea, va, ra) = split_evr(&this);
+    let (eb, vb, rb) = split_evr(&other);
+    let o = ea.cmp(&eb).then_with(|| rpmvercmp(va, vb));
+    // A missing release matches any release.
+    ordering(match (ra, rb) {
+        (Some(ra), Some(rb)) => o.then_with(|| rpmvercmp(ra, rb)),
+        _ => o,
+    })
+}
+
+/// dpkg's verrevcmp().
+fn verrevcmp(a: &str, b: &str) -> Ordering {
+    let order = |c: Option<u8>| -> i32 {
+        match c {
+            None => 0,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => c as i32,
+            Some(b'~') => -1,
+            Some(c) => c as i32 + 256,
+        }
+    };
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let (ac, bc) = (order(a.get(i).copied()), order(b.get(j).copied()));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+        while i < a.len() && a[i] == b'0' {
+            i += 1;
+        }
+        while j < b.len() && b[j] == b'0' {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while i < a.len() && a[i].is_ascii_digit() && j < b.len() && b[j].is_ascii_digit() {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if i < a.len() && a[i].is_ascii_digit() {
+            return Ordering::Greater;
+        }
+        if j < b.len() && b[j].is_ascii_digit() {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+/// `'1:9.2p1-2+deb12u3'.deb_compare('1:9.2p1-2') > 0`
+fn deb_compare(This(this): This<Arc<String>>, other: Arc<String>) -> i64 {
+    let (ea, va, ra) = split_evr(&this);
+    let (eb, vb, rb) = split_evr(&other);
+    ordering(
+        ea.cmp(&eb)
+            .then_with(|| verrevcmp(va, vb))
+            .then_with(|| verrevcmp(ra.unwrap_or(""), rb.unwrap_or(""))),
+    )
+}
+
+/// `'3.0.2'.semver_matches('>=3.0, <4')`
+fn semver_matches(
+    ftx: &FunctionContext,
+    This(this): This<Arc<String>>,
+    req: Arc<String>,
+) -> Result<bool> {
+    let version = semver::Version::parse(&this)
+        .map_err(|e| ftx.error(format!("'{}' not a valid version: {}", this, e)))?;
+    let req = semver::VersionReq::parse(&req)
+        .map_err(|e| ftx.error(format!("'{}' not a valid requirement: {}", req, e)))?;
+    Ok(req.matches(&version))
+}
+
+/// `'10.1.2.3'.in_cidr('10.0.0.0/8')`, IPv4 and IPv6.
+fn in_cidr(
+    ftx: &FunctionContext,
+    This(this): This<Arc<String>>,
+    cidr: Arc<String>,
+) -> Result<bool> {
+    let ip: IpAddr = this
+        .parse()
+        .map_err(|e| ftx.error(format!("'{}' not a valid address: {}", this, e)))?;
+    let invalid = |e: String| ftx.error(format!("'{}' not a valid CIDR: {}", cidr, e));
+    let (network, prefix) = cidr.split_once('/').unwrap_or((&cidr, ""));
+    let network: IpAddr = network.parse().map_err(|e| invalid(format!("{}", e)))?;
+    let bits: u32 = if network.is_ipv4() { 32 } else { 128 };
+    let prefix: u32 = if prefix.is_empty() {
+        bits
+    } else {
+        prefix.parse().map_err(|e| invalid(format!("{}", e)))?
+    };
+    if prefix > bits {
+        return Err(invalid(format!("prefix longer than {} bits", bits)));
+    }
+    let (ip, network) = match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            (u32::from(ip) as u128, u32::from(network) as u128)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => (u128::from(ip), u128::from(network)),
+        _ => return Ok(false),
+    };
+    let mask = if prefix == 0 {
+        0
+    } else {
+        (u128::MAX << (bits - prefix)) & (u128::MAX >> (128 - bits))
+    };
+    Ok(ip & mask == network & mask)
+}
+
+fn octal(ftx: &FunctionContext, value: &cel_interpreter::Value) -> Result<i64> {
+    match value {
+        cel_interpreter::Value::Int(n) => Ok(*n),
+        cel_interpreter::Value::UInt(n) => Ok(*n as i64),
+        cel_interpreter::Value::String(s) => i64::from_str_radix(s, 8)
+            .map_err(|_| ftx.error(format!("'{}' not a valid octal mode", s))),
+        value => Err(ftx.error(format!("Cannot process {:?}", value))),
+    }
+}
+
+/// `mode.perm_at_most('0644')`: no permission bit outside the mask is set. The mode is either
+/// an octal string or a number, e.g. produced by the `octal` coercion.
+fn perm_at_most(
+    ftx: &FunctionContext,
+    This(this): This<cel_interpreter::Value>,
+    mask: cel_interpreter::Value,
+) -> Result<bool> {
+    Ok(octal(ftx, &this)? & !octal(ftx, &mask)? & 0o7777 == 0)
+}
+
+/// `'/etc/ssh/sshd_config.d/50-redhat.conf'.glob_matches('/etc/ssh/**/*.conf')`
+///
+/// `*` and `?` do not match `/`, `**` does. Character classes (`[a-z]`, `[!0-9]`) are supported.
+fn glob_matches(
+    ftx: &FunctionContext,
+    regexes: &HashMap<String, regex::Regex>,
+    This(this): This<Arc<String>>,
+    pattern: Arc<String>,
+) -> Result<bool> {
+    Ok(regex(ftx, regexes, &glob_regex(&pattern))?.is_match(&this))
+}
+
+/// Anchored regex matching the same paths as the glob pattern.
+fn glob_regex(pattern: &str) -> String {
+    let mut expr = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                expr.push_str(".*");
+            }
+            '*' => expr.push_str("[^/]*"),
+            '?' => expr.push_str("[^/]"),
+            '[' => {
+                expr.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    expr.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        expr.push('\\');
+                    }
+                    expr.push(c);
+                }
+                expr.push(']');
+            }
+            c => expr.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    expr.push('$');
+    expr
+}
+
+fn each_value(
+    ftx: &FunctionContext,
+    this: cel_interpreter::Value,
+    ident: &Identifier,
+    expr: &Expression,
+    stop: bool,
+) -> Result<bool> {
+    match this {
+        cel_interpreter::Value::Map(m) => {
+            let mut ptx = ftx.ptx.new_inner_scope();
+            for value in m.map.values() {
+                ptx.add_variable_from_value(ident.0.as_str(), value.clone());
+                match ptx.resolve(expr)? {
+                    cel_interpreter::Value::Bool(b) if b == stop => return Ok(stop),
+                    cel_interpreter::Value::Bool(_) => (),
+                    value => return Err(ftx.error(format!("Expected bool, got {:?}", value))),
+                }
+            }
+            Ok(!stop)
+        }
+        value => Err(ftx.error(format!("Cannot process {:?}", value))),
+    }
+}
+
+/// `{'a': 1, 'b': 2}.all_values(v, v > 0)`: the built-in `all` ranges over map keys.
+fn all_values(
+    ftx: &FunctionContext,
+    This(this): This<cel_interpreter::Value>,
+    ident: Identifier,
+    expr: Expression,
+) -> Result<bool> {
+    each_value(ftx, this, &ident, &expr, false)
+}
+
+/// `{'a': 1, 'b': 2}.any_values(v, v > 1)`: the built-in `exists` ranges over map keys.
+fn any_values(
+    ftx: &FunctionContext,
+    This(this): This<cel_interpreter::Value>,
+    ident: Identifier,
+    expr: Expression,
+) -> Result<bool> {
+    each_value(ftx, this, &ident, &expr, true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,10 +697,11 @@ mod tests {
         let f = CELFilter {
             expr: "1 + int(args.number)".to_string(),
             args: Some(vec![("number".to_string(), 2.into())].into_iter().collect()),
+            program: Default::default(),
         };
 
         let sources = Map::<String, Value>::new();
-        assert_eq!(f.execute(&mut Runner::new(), &sources), 3);
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), 3);
     }
 
     #[test]
@@ -91,11 +709,12 @@ mod tests {
         let f = CELFilter {
             expr: "1 + int(src_1)".to_string(),
             args: None,
+            program: Default::default(),
         };
 
         let mut sources = Map::<String, Value>::new();
         sources.insert("src_1".to_string(), 3.into());
-        assert_eq!(f.execute(&mut Runner::new(), &sources), 4);
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), 4);
     }
 
     #[test]
@@ -103,10 +722,11 @@ mod tests {
         let f = CELFilter {
             expr: "{'a': 1}.has_value_of('a', 1)".to_string(),
             args: None,
+            program: Default::default(),
         };
 
         let sources = Map::<String, Value>::new();
-        assert_eq!(f.execute(&mut Runner::new(), &sources), true);
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), true);
     }
 
     #[test]
@@ -114,9 +734,196 @@ mod tests {
         let f = CELFilter {
             expr: "{'s': 'some,other'}.has_value_of('s', 'some')".to_string(),
             args: None,
+            program: Default::default(),
         };
 
         let sources = Map::<String, Value>::new();
-        assert_eq!(f.execute(&mut Runner::new(), &sources), true);
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), true);
+    }
+
+    #[test]
+    fn cel_filter_has_value_of_typed() {
+        let f = CELFilter {
+            expr: "src_1.has_value_of('CONFIG_LSM', 'yama') && src_1.has_value_of('CONFIG_MODULES', true)"
+                .to_string(),
+            args: None,
+            program: Default::default(),
+        };
+
+        let mut sources = Map::<String, Value>::new();
+        sources.insert(
+            "src_1".to_string(),
+            serde_json::json!({"CONFIG_LSM": ["lockdown", "yama"], "CONFIG_MODULES": true}),
+        );
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), true);
+    }
+
+    fn eval(expr: &str) -> Value {
+        let f = CELFilter {
+            expr: expr.to_string(),
+            args: None,
+            program: Default::default(),
+        };
+        f.execute(&mut Runner::new(), &Map::new()).unwrap()
+    }
+
+    fn eval_err(expr: &str) -> bool {
+        let f = CELFilter {
+            expr: expr.to_string(),
+            args: None,
+            program: Default::default(),
+        };
+        f.execute(&mut Runner::new(), &Map::new()).is_err()
+    }
+
+    #[test]
+    fn cel_filter_matches() {
+        assert_eq!(
+            eval("'PermitRootLogin no'.matches('^PermitRootLogin')"),
+            true
+        );
+        assert_eq!(eval("'PermitRootLogin no'.matches('^Port')"), false);
+        assert!(eval_err("'x'.matches('(')"));
+    }
+
+    #[test]
+    fn cel_filter_compile_literal_regexes() {
+        let compile = |expr: &str| {
+            CELFilter {
+                expr: expr.to_string(),
+                args: None,
+                program: Default::default(),
+            }
+            .compile()
+        };
+        assert!(compile("'x'.matches('(')").is_err());
+        assert!(compile("['x'].all(s, s.captures('^(x'))").is_err());
+        assert!(compile("'x'.glob_matches('[')").is_err());
+        assert!(compile("'x'.matches('^x$') && 'x'.glob_matches('/etc/*')").is_ok());
+        // Patterns computed at run time are compiled when the function is called.
+        assert!(compile("'x'.matches('(' + 'x')").is_ok());
+        assert_eq!(eval("'(x'.matches('^' + '\\\\(x')"), true);
+    }
+
+    #[test]
+    fn cel_filter_captures() {
+        assert_eq!(
+            eval("'umask 027'.captures('^(umask) ([0-7]+)(x)?$')"),
+            serde_json::json!(["umask 027", "umask", "027", ""])
+        );
+        assert_eq!(
+            eval("'umask 027'.captures('^(\\\\w+) (\\\\d+)$')[2]"),
+            "027"
+        );
+        assert_eq!(
+            eval("'umask'.captures('^(\\\\d+)$')"),
+            serde_json::json!([])
+        );
+    }
+
+    #[test]
+    fn cel_filter_rpm_compare() {
+        assert_eq!(eval("'1.0-1'.rpm_compare('1.0-1')"), 0);
+        assert_eq!(eval("'1.0-1'.rpm_compare('1.0-2')"), -1);
+        assert_eq!(eval("'1.10'.rpm_compare('1.9')"), 1);
+        assert_eq!(eval("'1.0a'.rpm_compare('1.0')"), 1);
+        assert_eq!(eval("'1.0~rc1'.rpm_compare('1.0')"), -1);
+        assert_eq!(eval("'1.0^git1'.rpm_compare('1.0')"), 1);
+        assert_eq!(eval("'1.0^git1'.rpm_compare('1.0.1')"), -1);
+        assert_eq!(eval("'1:1.0-1'.rpm_compare('2.0-1')"), 1);
+        assert_eq!(eval("'8.7p1-38.el9'.rpm_compare('8.7p1-34.el9_3.3')"), 1);
+        assert_eq!(eval("'2.0'.rpm_compare('2.0-5.el9')"), 0);
+    }
+
+    #[test]
+    fn cel_filter_deb_compare() {
+        assert_eq!(eval("'1.0-1'.deb_compare('1.0-1')"), 0);
+        assert_eq!(eval("'1.0~rc1-1'.deb_compare('1.0-1')"), -1);
+        assert_eq!(eval("'1.0-1'.deb_compare('1.0-1+deb12u1')"), -1);
+        assert_eq!(eval("'1:9.2p1-2'.deb_compare('9.9p1-1')"), 1);
+        assert_eq!(eval("'2.36-9+deb12u4'.deb_compare('2.36-9+deb12u10')"), -1);
+        assert_eq!(eval("'1.0a'.deb_compare('1.0+')"), -1);
+        assert_eq!(eval("'1.0'.deb_compare('1.0-0')"), 0);
+    }
+
+    #[test]
+    fn cel_filter_semver_matches() {
+        assert_eq!(eval("'3.0.2'.semver_matches('>=3.0, <4')"), true);
+        assert_eq!(eval("'1.1.1'.semver_matches('>=3.0')"), false);
+        assert!(eval_err("'3.0'.semver_matches('>=3.0')"));
+    }
+
+    #[test]
+    fn cel_filter_in_cidr() {
+        assert_eq!(eval("'10.1.2.3'.in_cidr('10.0.0.0/8')"), true);
+        assert_eq!(eval("'192.168.1.1'.in_cidr('10.0.0.0/8')"), false);
+        assert_eq!(eval("'192.168.1.1'.in_cidr('0.0.0.0/0')"), true);
+        assert_eq!(eval("'127.0.0.1'.in_cidr('127.0.0.1')"), true);
+        assert_eq!(eval("'fe80::1'.in_cidr('fe80::/10')"), true);
+        assert_eq!(eval("'2001:db8::1'.in_cidr('fe80::/10')"), false);
+        assert_eq!(eval("'::1'.in_cidr('127.0.0.0/8')"), false);
+        assert!(eval_err("'10.0.0.1'.in_cidr('10.0.0.0/33')"));
+        assert!(eval_err("'localhost'.in_cidr('10.0.0.0/8')"));
+    }
+
+    #[test]
+    fn cel_filter_perm_at_most() {
+        assert_eq!(eval("'0644'.perm_at_most('0644')"), true);
+        assert_eq!(eval("'0600'.perm_at_most('0644')"), true);
+        assert_eq!(eval("'0664'.perm_at_most('0644')"), false);
+        assert_eq!(eval("'4755'.perm_at_most('0755')"), false);
+        assert_eq!(eval("420.perm_at_most('0644')"), true);
+        assert_eq!(eval("436.perm_at_most(420)"), false);
+        assert!(eval_err("'0999'.perm_at_most('0644')"));
+    }
+
+    #[test]
+    fn cel_filter_glob_matches() {
+        assert_eq!(
+            eval("'/etc/modprobe.d/cis.conf'.glob_matches('/etc/modprobe.d/*.conf')"),
+            true
+        );
+        assert_eq!(
+            eval("'/etc/modprobe.d/a/cis.conf'.glob_matches('/etc/modprobe.d/*.conf')"),
+            false
+        );
+        assert_eq!(
+            eval("'/etc/modprobe.d/a/cis.conf'.glob_matches('/etc/**.conf')"),
+            true
+        );
+        assert_eq!(eval("'/dev/tty1'.glob_matches('/dev/tty[0-9]')"), true);
+        assert_eq!(eval("'/dev/ttyS'.glob_matches('/dev/tty[!0-9]')"), true);
+        assert_eq!(eval("'/dev/tty1'.glob_matches('/dev/tty?')"), true);
+        assert_eq!(eval("'a+b'.glob_matches('a+b')"), true);
+    }
+
+    #[test]
+    fn cel_filter_all_any_values() {
+        assert_eq!(eval("{'a': 1, 'b': 2}.all_values(v, v > 0)"), true);
+        assert_eq!(eval("{'a': 1, 'b': 2}.all_values(v, v > 1)"), false);
+        assert_eq!(eval("{'a': 1, 'b': 2}.any_values(v, v > 1)"), true);
+        assert_eq!(eval("{'a': 1, 'b': 2}.any_values(v, v > 2)"), false);
+        assert_eq!(eval("{}.all_values(v, v > 2)"), true);
+        assert!(eval_err("[1].all_values(v, v > 0)"));
+        assert!(eval_err("{'a': 1}.any_values(v, v)"));
+    }
+
+    #[test]
+    fn cel_filter_references() {
+        let f = CELFilter {
+            expr: "src_1[0].data.all(m, m.uid == int(args.uid)) &&\n  'src_2' != src_2.src_3 && [1].exists(src_1, src_1 > 0) && type(src_4) == map"
+                .to_string(),
+            args: None,
+            program: Default::default(),
+        };
+
+        assert_eq!(
+            f.references(),
+            Some(vec![
+                "src_1".to_string(),
+                "src_2".to_string(),
+                "src_4".to_string()
+            ])
+        );
     }
 }
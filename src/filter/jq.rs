@@ -0,0 +1,224 @@
+use jaq_core::load::{self, Arena, File, Loader};
+use jaq_core::{compile, Compiler, Ctx, Native, RcIter};
+use jaq_json::Val;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::compiled::Compiled;
+use crate::error::{Error, Result};
+use crate::filter::{line_column, Executable};
+use crate::runner::Runner;
+
+/// Runs a jq program over an object holding the block results under their ids and the
+/// arguments under `args`, e.g. `.sshd_config[0].data.global.permitrootlogin == "no"`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct JQFilter {
+    pub expr: String,
+    pub args: Option<Map<String, Value>>,
+    #[serde(skip)]
+    pub program: Compiled<jaq_core::Filter<Native<Val>>>,
+}
+
+/// Line and column of a slice of the program, used by the jq errors to point at the problem.
+fn position(code: &str, part: &str) -> String {
+    let offset = (part.as_ptr() as usize)
+        .checked_sub(code.as_ptr() as usize)
+        .filter(|o| *o <= code.len() && code.is_char_boundary(*o));
+    match offset.map(|offset| line_column(code, offset)) {
+        Some((line, column)) => format!("{}:{}", line, column),
+        None => "?".to_string(),
+    }
+}
+
+fn load_errors(code: &str, errors: load::Errors<&str, ()>) -> String {
+    let mut messages = Vec::new();
+    for (_, error) in errors {
+        match error {
+            load::Error::Io(errors) => {
+                for (path, e) in errors {
+                    messages.push(format!("can't load '{}': {}", path, e));
+                }
+            }
+            load::Error::Lex(errors) => {
+                for (expect, found) in errors {
+                    messages.push(format!(
+                        "expected {} at {}",
+                        expect.as_str(),
+                        position(code, found)
+                    ));
+                }
+            }
+            load::Error::Parse(errors) => {
+                for (expect, found) in errors {
+                    messages.push(format!(
+                        "expected {} at {}",
+                        expect.as_str(),
+                        position(code, found)
+                    ));
+                }
+            }
+        }
+    }
+    messages.join(", ")
+}
+
+fn compile_errors(code: &str, errors: compile::Errors<&str, ()>) -> String {
+    let mut messages = Vec::new();
+    for (_, errors) in errors {
+        for (name, undefined) in errors {
+            let kind = match undefined {
+                compile::Undefined::Mod => "module".to_string(),
+                compile::Undefined::Var => "variable".to_string(),
+                compile::Undefined::Label => "label".to_string(),
+                compile::Undefined::Filter(arity) => format!("filter/{}", arity),
+                _ => "symbol".to_string(),
+            };
+            messages.push(format!(
+                "undefined {} '{}' at {}",
+                kind,
+                name,
+                position(code, name)
+            ));
+        }
+    }
+    messages.join(", ")
+}
+
+impl JQFilter {
+    fn program(&self) -> Result<&jaq_core::Filter<Native<Val>>> {
+        self.program.get_or_try_init(|| {
+            let code = self.expr.as_str();
+            let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+            let arena = Arena::default();
+            let modules = loader
+                .load(&arena, File { code, path: () })
+                .map_err(|e| Error::Filter(load_errors(code, e)))?;
+            Compiler::default()
+                .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+                .compile(modules)
+                .map_err(|e| Error::Filter(compile_errors(code, e)))
+        })
+    }
+}
+
+impl Executable for JQFilter {
+    fn compile(&self) -> Result<()> {
+        self.program().map(|_| ())
+    }
+
+    fn execute(&self, _: &mut Runner, sources: &Map<String, Value>) -> Result<Value> {
+        let program = self.program()?;
+        let mut input = sources.clone();
+        input.insert(
+            "args".to_string(),
+            self.args.clone().map(Value::Object).unwrap_or_default(),
+        );
+
+        let inputs = RcIter::new(core::iter::empty());
+        // Like jq, a program may yield any number of values: none gives null, several a list.
+        let mut values = program
+            .run((Ctx::new([], &inputs), Val::from(Value::Object(input))))
+            .map(|v| v.map(Value::from).map_err(|e| Error::Filter(e.to_string())))
+            .collect::<Result<Vec<Value>>>()?;
+        Ok(match values.len() {
+            0 => Value::Null,
+            1 => values.remove(0),
+            _ => Value::Array(values),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jq_filter() {
+        let f = JQFilter {
+            expr: "1 + .args.number".to_string(),
+            args: Some(vec![("number".to_string(), 2.into())].into_iter().collect()),
+            program: Default::default(),
+        };
+
+        let sources = Map::<String, Value>::new();
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), 3);
+    }
+
+    #[test]
+    fn jq_filter_with_source() {
+        let f = JQFilter {
+            expr: r#"[.mounts[0].data[] | select(.options | index("nodev") | not) | .mount_point]"#
+                .to_string(),
+            args: None,
+            program: Default::default(),
+        };
+
+        let mut sources = Map::<String, Value>::new();
+        sources.insert(
+            "mounts".to_string(),
+            serde_json::json!([{"source": "/proc/self/mounts", "data": [
+                {"mount_point": "/tmp", "options": ["rw", "nodev", "nosuid"]},
+                {"mount_point": "/home", "options": ["rw", "relatime"]},
+            ]}]),
+        );
+        assert_eq!(
+            f.execute(&mut Runner::new(), &sources).unwrap(),
+            serde_json::json!(["/home"])
+        );
+    }
+
+    #[test]
+    fn jq_filter_several_values() {
+        let f = JQFilter {
+            expr: ".src_1[]".to_string(),
+            args: None,
+            program: Default::default(),
+        };
+
+        let mut sources = Map::<String, Value>::new();
+        sources.insert("src_1".to_string(), serde_json::json!([1, 2]));
+        assert_eq!(
+            f.execute(&mut Runner::new(), &sources).unwrap(),
+            serde_json::json!([1, 2])
+        );
+        sources.insert("src_1".to_string(), serde_json::json!([]));
+        assert_eq!(
+            f.execute(&mut Runner::new(), &sources).unwrap(),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn jq_filter_errors() {
+        let f = JQFilter {
+            expr: ".src_1 |".to_string(),
+            args: None,
+            program: Default::default(),
+        };
+        assert!(matches!(f.compile(), Err(Error::Filter(_))));
+
+        let f = JQFilter {
+            expr: ".src_1 | no_such_filter".to_string(),
+            args: None,
+            program: Default::default(),
+        };
+        assert_eq!(
+            f.compile(),
+            Err(Error::Filter(
+                "undefined filter/0 'no_such_filter' at 1:10".to_string()
+            ))
+        );
+
+        let f = JQFilter {
+            expr: r#"error("bad")"#.to_string(),
+            args: None,
+            program: Default::default(),
+        };
+        let sources = Map::<String, Value>::new();
+        assert!(matches!(
+            f.execute(&mut Runner::new(), &sources),
+            Err(Error::Filter(_))
+        ));
+    }
+}
@@ -3,26 +3,84 @@ use regorus::Engine;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-use crate::Executable;
-use crate::Runner;
+use crate::compiled::Compiled;
+use crate::error::{Error, Result};
+use crate::filter::Executable;
+use crate::runner::Runner;
 
+/// Evaluates a Rego query. Block results are exposed as `input` and `args`
+/// are loaded as `data`; an optional `policy` module is loaded before the
+/// query runs so that `expr` can refer to its rules, e.g. `data.pkg.allow`.
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct REGOFilter {
     pub expr: String,
-    pub args: Option<HashMap<String, Value>>,
+    pub policy: Option<String>,
+    pub args: Option<Map<String, Value>>,
+    #[serde(skip)]
+    pub engine: Compiled<Box<Engine>>,
+}
+
+fn to_rego(value: Value) -> Result<regorus::Value> {
+    regorus::Value::from_json_str(&value.to_string()).map_err(|e| Error::Filter(e.to_string()))
+}
+
+fn from_rego(value: &regorus::Value) -> Result<Value> {
+    if *value == regorus::Value::Undefined {
+        return Ok(Value::Null);
+    }
+    let json = value
+        .to_json_str()
+        .map_err(|e| Error::Filter(e.to_string()))?;
+    serde_json::from_str(&json).map_err(|e| Error::Filter(e.to_string()))
+}
+
+impl REGOFilter {
+    /// The engine with the policy parsed and the arguments loaded, ready to take an input.
+    fn engine(&self) -> Result<&Engine> {
+        self.engine
+            .get_or_try_init(|| {
+                let mut engine = Engine::new();
+                if let Some(policy) = &self.policy {
+                    engine
+                        .add_policy("policy.rego".to_string(), policy.clone())
+                        .map_err(|e| Error::Filter(e.to_string()))?;
+                }
+                if let Some(args) = &self.args {
+                    engine
+                        .add_data(to_rego(Value::Object(args.clone()))?)
+                        .map_err(|e| Error::Filter(e.to_string()))?;
+                }
+                Ok(Box::new(engine))
+            })
+            .map(|engine| &**engine)
+    }
 }
 
 impl Executable for REGOFilter {
-    fn execute(&self, _: &Runner) -> Value {
-        let mut engine = Engine::new();
-        let args_json = serde_json::to_string(&self.args).unwrap();
-        engine
-            .add_data(regorus::Value::from_json_str(&args_json).unwrap())
-            .unwrap();
-        let r = engine.eval_query(self.expr.clone(), true).unwrap();
-        let rego_value = &r.result[0].expressions[0].value;
-        println!("{:?}", rego_value);
-        serde_json::from_str(&rego_value.to_json_str().unwrap()).expect("REGO -> SERDE error!")
+    fn compile(&self) -> Result<()> {
+        self.engine().map(|_| ())
+    }
+
+    fn execute(&self, _: &mut Runner, sources: &Map<String, Value>) -> Result<Value> {
+        let mut engine = self.engine()?.clone();
+        engine.set_input(to_rego(Value::Object(sources.clone()))?);
+
+        let results = engine
+            .eval_query(self.expr.clone(), false)
+            .map_err(|e| Error::Filter(e.to_string()))?;
+        // The value of a query is the value of its last expression. An
+        // undefined query yields null, several solutions yield a list.
+        let mut values = results
+            .result
+            .iter()
+            .filter_map(|r| r.expressions.last())
+            .map(|e| from_rego(&e.value))
+            .collect::<Result<Vec<Value>>>()?;
+        Ok(match values.len() {
+            0 => Value::Null,
+            1 => values.remove(0),
+            _ => Value::Array(values),
+        })
     }
 }
 
@@ -34,9 +92,101 @@ mod tests {
     fn rego_filter() {
         let f = REGOFilter {
             expr: "1 + data.number".to_string(),
+            policy: None,
             args: Some(vec![("number".to_string(), 2.into())].into_iter().collect()),
+            engine: Default::default(),
+        };
+
+        let sources = Map::<String, Value>::new();
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), 3);
+    }
+
+    #[test]
+    fn rego_filter_with_source() {
+        let f = REGOFilter {
+            expr: "1 + input.src_1".to_string(),
+            policy: None,
+            args: None,
+            engine: Default::default(),
         };
 
-        assert_eq!(f.execute(&Runner::new()), 3);
+        let mut sources = Map::<String, Value>::new();
+        sources.insert("src_1".to_string(), 3.into());
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), 4);
+    }
+
+    #[test]
+    fn rego_filter_policy() {
+        let f = REGOFilter {
+            expr: "data.lockdown.allow".to_string(),
+            policy: Some(
+                r#"
+                package lockdown
+                import rego.v1
+
+                default allow := false
+
+                allow if {
+                    some p in input.processes
+                    p.comm == data.daemon
+                    p.uid == 0
+                }
+                "#
+                .to_string(),
+            ),
+            args: Some(
+                vec![("daemon".to_string(), "sshd".into())]
+                    .into_iter()
+                    .collect(),
+            ),
+            engine: Default::default(),
+        };
+
+        let mut sources = Map::<String, Value>::new();
+        sources.insert(
+            "processes".to_string(),
+            serde_json::json!([{"comm": "bash", "uid": 1000}, {"comm": "sshd", "uid": 0}]),
+        );
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), true);
+
+        sources.insert(
+            "processes".to_string(),
+            serde_json::json!([{"comm": "sshd", "uid": 1000}]),
+        );
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), false);
+    }
+
+    #[test]
+    fn rego_filter_undefined() {
+        let f = REGOFilter {
+            expr: "input.missing".to_string(),
+            policy: None,
+            args: None,
+            engine: Default::default(),
+        };
+
+        let sources = Map::<String, Value>::new();
+        assert_eq!(
+            f.execute(&mut Runner::new(), &sources).unwrap(),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn rego_filter_error() {
+        let f = REGOFilter {
+            expr: "data.p.allow".to_string(),
+            policy: Some("package p\nallow {".to_string()),
+            args: None,
+            engine: Default::default(),
+        };
+
+        assert!(matches!(f.compile(), Err(Error::Filter(_))));
+
+        let sources = Map::<String, Value>::new();
+        assert!(matches!(
+            f.execute(&mut Runner::new(), &sources),
+            Err(Error::Filter(_))
+        ));
     }
 }
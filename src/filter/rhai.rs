@@ -0,0 +1,184 @@
+use ::rhai::module_resolvers::DummyModuleResolver;
+use ::rhai::{Dynamic, Engine, Scope, AST};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use simplelog::debug;
+
+use crate::compiled::Compiled;
+use crate::error::{Error, Result};
+use crate::filter::Executable;
+use crate::runner::Runner;
+
+/// Limits keeping a script from spinning or exhausting the memory of the scanned host.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_STRING_SIZE: usize = 1 << 20;
+const MAX_ARRAY_SIZE: usize = 100_000;
+const MAX_MAP_SIZE: usize = 100_000;
+
+/// Runs a Rhai script, the value of its last statement is the result. Block results are
+/// variables named after the blocks and `args` holds the arguments. Modules can't be
+/// imported and the script runs under operation and size limits.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RHAIFilter {
+    pub script: String,
+    pub args: Option<Map<String, Value>>,
+    #[serde(skip)]
+    pub ast: Compiled<AST>,
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(MAX_MAP_SIZE)
+        .on_print(|s| debug!("Rhai: {}", s))
+        .on_debug(|s, _, pos| debug!("Rhai {}: {}", pos, s));
+    engine
+}
+
+fn to_dynamic(value: &Value) -> Result<Dynamic> {
+    ::rhai::serde::to_dynamic(value).map_err(|e| Error::Filter(e.to_string()))
+}
+
+impl RHAIFilter {
+    fn ast(&self, engine: &Engine) -> Result<&AST> {
+        self.ast.get_or_try_init(|| {
+            engine
+                .compile(&self.script)
+                .map_err(|e| Error::Filter(format!("Can't compile the script: {}", e)))
+        })
+    }
+}
+
+impl Executable for RHAIFilter {
+    fn compile(&self) -> Result<()> {
+        self.ast(&engine()).map(|_| ())
+    }
+
+    fn execute(&self, _: &mut Runner, sources: &Map<String, Value>) -> Result<Value> {
+        let engine = engine();
+        let ast = self.ast(&engine)?;
+        let mut scope = Scope::new();
+        let args = self.args.clone().map(Value::Object).unwrap_or_default();
+        scope.push_constant_dynamic("args", to_dynamic(&args)?);
+        for (id, value) in sources {
+            scope.push_constant_dynamic(id.as_str(), to_dynamic(value)?);
+        }
+        let result: Dynamic = engine
+            .eval_ast_with_scope(&mut scope, ast)
+            .map_err(|e| Error::Filter(e.to_string()))?;
+        ::rhai::serde::from_dynamic(&result).map_err(|e| Error::Filter(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rhai_filter() {
+        let f = RHAIFilter {
+            script: "1 + args.number".to_string(),
+            args: Some(vec![("number".to_string(), 2.into())].into_iter().collect()),
+            ast: Default::default(),
+        };
+
+        let sources = Map::<String, Value>::new();
+        assert_eq!(f.execute(&mut Runner::new(), &sources).unwrap(), 3);
+    }
+
+    #[test]
+    fn rhai_filter_with_state() {
+        let f = RHAIFilter {
+            script: r#"
+                let violations = [];
+                let seen = #{};
+                for line in src_1[0].data {
+                    let fields = line.split(":");
+                    if fields[2] == "0" && fields[0] != "root" {
+                        violations.push(fields[0]);
+                    }
+                    if fields[0] in seen {
+                        violations.push("duplicate " + fields[0]);
+                    }
+                    seen[fields[0]] = true;
+                }
+                violations
+            "#
+            .to_string(),
+            args: None,
+            ast: Default::default(),
+        };
+
+        let mut sources = Map::<String, Value>::new();
+        sources.insert(
+            "src_1".to_string(),
+            serde_json::json!([{"source": "/etc/passwd", "data": [
+                "root:x:0:0::/root:/bin/bash",
+                "toor:x:0:0::/root:/bin/bash",
+                "bin:x:1:1::/:/sbin/nologin",
+                "bin:x:2:2::/:/sbin/nologin",
+            ]}]),
+        );
+        assert_eq!(
+            f.execute(&mut Runner::new(), &sources).unwrap(),
+            serde_json::json!(["toor", "duplicate bin"])
+        );
+    }
+
+    #[test]
+    fn rhai_filter_limits() {
+        let f = RHAIFilter {
+            script: "loop {}".to_string(),
+            args: None,
+            ast: Default::default(),
+        };
+
+        let sources = Map::<String, Value>::new();
+        assert!(matches!(
+            f.execute(&mut Runner::new(), &sources),
+            Err(Error::Filter(_))
+        ));
+
+        let f = RHAIFilter {
+            script: r#"let s = "x"; loop { s += s; }"#.to_string(),
+            args: None,
+            ast: Default::default(),
+        };
+        assert!(matches!(
+            f.execute(&mut Runner::new(), &sources),
+            Err(Error::Filter(_))
+        ));
+    }
+
+    #[test]
+    fn rhai_filter_no_import() {
+        let f = RHAIFilter {
+            script: r#"import "/etc/passwd" as p; 1"#.to_string(),
+            args: None,
+            ast: Default::default(),
+        };
+
+        let sources = Map::<String, Value>::new();
+        assert!(f.execute(&mut Runner::new(), &sources).is_err());
+    }
+
+    #[test]
+    fn rhai_filter_syntax_error() {
+        let f = RHAIFilter {
+            script: "let x = ;".to_string(),
+            args: None,
+            ast: Default::default(),
+        };
+
+        assert!(matches!(f.compile(), Err(Error::Filter(_))));
+    }
+}
@@ -1,12 +1,15 @@
 use simplelog::*;
 
 use runner::Runner;
-use serde_json;
 
 pub mod block;
+pub mod compiled;
+pub mod error;
 pub mod filter;
 pub mod probe;
+pub mod report;
 pub mod runner;
+pub mod trace;
 pub mod wrapper;
 
 use argh::FromArgs;
@@ -22,6 +25,18 @@ pub struct Options {
     #[argh(switch, short = 'V')]
     verbose: bool,
 
+    /// trace the execution of every block to stderr as JSON, optional
+    #[argh(switch)]
+    trace: bool,
+
+    /// write the traces to a JSON file instead of stderr, implies --trace, optional
+    #[argh(option)]
+    trace_output: Option<String>,
+
+    /// report format, optional, supported formats: [json] result document, default; [junit] JUnit XML
+    #[argh(option, default = "report::Format::Json")]
+    format: report::Format,
+
     /// test file(s), at least one is required
     #[argh(positional)]
     inputs: Vec<String>,
@@ -44,20 +59,66 @@ pub fn lib_main(opts: Options) {
     info!("Inputs: {:?}", opts.inputs);
 
     let mut runner = Runner::new_with_target(&opts.target);
-    let tests = load_tests(&opts.inputs);
+    let mut report = report::Report::new(&opts.target, vec![]);
+    let tests = load_tests(&opts.inputs, &mut report).unwrap_or_else(|e| {
+        error!("{}", e);
+        // The run did not complete, the document has no tests and no finish time.
+        println!("{}", report.render(opts.format));
+        std::process::exit(1);
+    });
+    let tracing = opts.trace || opts.trace_output.is_some();
+    run_tests(&mut runner, &tests, &mut report, tracing);
+    if opts.trace_output.is_none() {
+        for trace in report.tests.iter().filter_map(|t| t.trace.as_ref()) {
+            eprintln!("{}", serde_json::to_string_pretty(trace).unwrap());
+        }
+    }
+    report.finish();
+    println!("{}", report.render(opts.format));
 
+    if let Some(path) = &opts.trace_output {
+        let traces: Vec<&trace::Trace> = report
+            .tests
+            .iter()
+            .filter_map(|t| t.trace.as_ref())
+            .collect();
+        std::fs::write(path, serde_json::to_string_pretty(&traces).unwrap())
+            .unwrap_or_else(|e| panic!("Unable to write the trace to '{}': {}", path, e));
+    }
+}
+
+/// Runs the tests in order and adds them to the report, with their trace when `tracing`.
+pub fn run_tests(
+    runner: &mut Runner,
+    tests: &[block::Test],
+    report: &mut report::Report,
+    tracing: bool,
+) {
     for test in tests {
-        let result = test.execute(&mut runner);
-        println!("{}", serde_json::to_string_pretty(&result).unwrap());
+        let id = block::Entity::get_id(test);
+        let mut trace = tracing.then(|| trace::Trace::new(id));
+        let test_report = report.add_test(id, || match &mut trace {
+            Some(trace) => test.execute_with_trace(runner, trace),
+            None => test.execute(runner),
+        });
+        for e in &test_report.errors {
+            error!("Test '{}' failed: {}", id, e);
+        }
+        test_report.trace = trace;
     }
 }
 
-fn load_tests(inputs: &Vec<String>) -> Vec<block::Test> {
-    let tests: Vec<block::Test> = inputs
-        .iter()
-        .map(|i| block::Test::create_from_json(std::fs::read_to_string(i).unwrap()).unwrap())
-        .collect();
-    tests
+/// Loads and compiles every test, adding the content of each input read to the report. The
+/// error names the first input which can't be loaded.
+fn load_tests(inputs: &[String], report: &mut report::Report) -> Result<Vec<block::Test>, String> {
+    let mut tests = Vec::new();
+    for i in inputs {
+        let error = |e: String| format!("Unable to load test '{}': {}", i, e);
+        let json = std::fs::read_to_string(i).map_err(|e| error(e.to_string()))?;
+        report.run.content.push(report::Content::new(i, &json));
+        tests.push(block::Test::create_from_json(json).map_err(|e| error(e.to_string()))?);
+    }
+    Ok(tests)
 }
 
 pub trait Entity {
@@ -70,6 +131,24 @@ mod tests {
 
     #[test]
     fn test_load_content_test_1() {
-        let _tests = load_tests(&vec!["content/test_1.json".to_string()]);
+        let mut report = report::Report::new(&None, vec![]);
+        let _tests = load_tests(&["content/test_1.json".to_string()], &mut report).unwrap();
+        assert_eq!(report.run.content[0].path, "content/test_1.json");
+    }
+
+    #[test]
+    fn test_load_missing_content() {
+        let mut report = report::Report::new(&None, vec![]);
+        let error = load_tests(
+            &[
+                "content/test_1.json".to_string(),
+                "content/missing.json".to_string(),
+            ],
+            &mut report,
+        )
+        .unwrap_err();
+        assert!(error.starts_with("Unable to load test 'content/missing.json': "));
+        // Only the inputs which could be read are hashed.
+        assert_eq!(report.run.content.len(), 1);
     }
 }
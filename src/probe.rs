@@ -1,12 +1,32 @@
 use serde::{Deserialize, Serialize};
 
+pub mod accounts;
+pub mod kmod;
+pub mod mounts;
+pub mod platform;
+pub mod processes;
+pub mod sockets;
+
 use crate::runner::Runner;
 
+use crate::probe::accounts::AccountsProbe;
+use crate::probe::kmod::KmodProbe;
+use crate::probe::mounts::MountsProbe;
+use crate::probe::platform::PlatformProbe;
+use crate::probe::processes::ProcessesProbe;
+use crate::probe::sockets::SocketsProbe;
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Probe {
     Process(ProcessProbe),
     File(FileProbe),
+    Processes(ProcessesProbe),
+    Sockets(SocketsProbe),
+    Accounts(AccountsProbe),
+    Kmod(KmodProbe),
+    Mounts(MountsProbe),
+    Platform(PlatformProbe),
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -29,16 +49,31 @@ impl Runable for Probe {
         match self {
             Probe::Process(probe) => probe.run(runner),
             Probe::File(probe) => probe.run(runner),
+            Probe::Processes(probe) => probe.run(runner),
+            Probe::Sockets(probe) => probe.run(runner),
+            Probe::Accounts(probe) => probe.run(runner),
+            Probe::Kmod(probe) => probe.run(runner),
+            Probe::Mounts(probe) => probe.run(runner),
+            Probe::Platform(probe) => probe.run(runner),
         }
     }
 }
 
 impl Runable for FileProbe {
     fn run(&self, runner: &mut Runner) -> Vec<(String, String)> {
-        self.paths
-            .iter()
-            .map(|path| (path.clone(), runner.cat(&path)))
-            .collect()
+        let mut outputs = Vec::new();
+        for path in &self.paths {
+            // Globs (e.g. drop-in directories) expand to every matching file in order.
+            if path.contains(['*', '?', '[']) {
+                for path in runner.unglob_path(path) {
+                    let output = runner.cat(&path);
+                    outputs.push((path, output));
+                }
+            } else {
+                outputs.push((path.clone(), runner.cat(path)));
+            }
+        }
+        outputs
     }
 }
 
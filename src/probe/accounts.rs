@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::probe::Runable;
+use crate::runner::Runner;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AccountsProbe {
+    #[serde(default = "default_root")]
+    pub root: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Password {
+    pub status: String,
+    pub algorithm: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Aging {
+    pub last_change: Option<i64>,
+    pub min_days: Option<i64>,
+    pub max_days: Option<i64>,
+    pub warn_days: Option<i64>,
+    pub inactive_days: Option<i64>,
+    pub expire_date: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct UserRecord {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub group: Option<String>,
+    pub groups: Vec<String>,
+    pub gecos: String,
+    pub home: String,
+    pub shell: String,
+    pub password: Option<Password>,
+    pub aging: Option<Aging>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct GroupRecord {
+    pub name: String,
+    pub gid: u32,
+    pub members: Vec<String>,
+    pub admins: Vec<String>,
+    pub password: Option<Password>,
+}
+
+fn default_root() -> String {
+    "/etc".to_string()
+}
+
+impl Runable for AccountsProbe {
+    fn run(&self, runner: &mut Runner) -> Vec<(String, String)> {
+        let passwd_path = format!("{}/passwd", self.root);
+        let group_path = format!("{}/group", self.root);
+        // Shadow files are usually readable by root only, the related fields stay empty otherwise.
+        let shadow = parse_shadow(&runner.cat(&format!("{}/shadow", self.root)));
+        let gshadow = parse_shadow(&runner.cat(&format!("{}/gshadow", self.root)));
+
+        let groups = parse_group(&runner.cat(&group_path), &gshadow);
+        let users = parse_passwd(&runner.cat(&passwd_path), &shadow, &groups);
+
+        vec![
+            (
+                passwd_path,
+                serde_json::to_string(&users).expect("Can't serialize users!"),
+            ),
+            (
+                group_path,
+                serde_json::to_string(&groups).expect("Can't serialize groups!"),
+            ),
+        ]
+    }
+}
+
+fn entries(content: &str) -> impl Iterator<Item = Vec<&str>> {
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+        .map(|l| l.split(':').collect())
+}
+
+fn names(field: &str) -> Vec<String> {
+    field
+        .split(',')
+        .filter(|n| !n.is_empty())
+        .map(|n| n.to_string())
+        .collect()
+}
+
+fn parse_shadow(content: &str) -> HashMap<String, Vec<String>> {
+    entries(content)
+        .filter(|e| e.len() > 1)
+        .map(|e| {
+            (
+                e[0].to_string(),
+                e[1..].iter().map(|f| f.to_string()).collect(),
+            )
+        })
+        .collect()
+}
+
+fn parse_group(content: &str, gshadow: &HashMap<String, Vec<String>>) -> Vec<GroupRecord> {
+    entries(content)
+        .filter(|e| e.len() == 4)
+        .filter_map(|e| {
+            let shadowed = gshadow.get(e[0]);
+            Some(GroupRecord {
+                name: e[0].to_string(),
+                gid: e[2].parse().ok()?,
+                members: names(e[3]),
+                admins: shadowed
+                    .and_then(|s| s.get(1))
+                    .map(|a| names(a))
+                    .unwrap_or_default(),
+                password: shadowed.map(|s| password_status(&s[0])),
+            })
+        })
+        .collect()
+}
+
+fn parse_passwd(
+    content: &str,
+    shadow: &HashMap<String, Vec<String>>,
+    groups: &[GroupRecord],
+) -> Vec<UserRecord> {
+    entries(content)
+        .filter(|e| e.len() == 7)
+        .filter_map(|e| {
+            let name = e[0].to_string();
+            let gid = e[3].parse().ok()?;
+            let shadowed = shadow.get(&name);
+            Some(UserRecord {
+                uid: e[2].parse().ok()?,
+                gid,
+                group: groups.iter().find(|g| g.gid == gid).map(|g| g.name.clone()),
+                groups: groups
+                    .iter()
+                    .filter(|g| g.members.contains(&name))
+                    .map(|g| g.name.clone())
+                    .collect(),
+                gecos: e[4].to_string(),
+                home: e[5].to_string(),
+                shell: e[6].to_string(),
+                password: shadowed.map(|s| password_status(&s[0])),
+                aging: shadowed.map(|s| {
+                    let day = |i: usize| s.get(i).and_then(|d| d.parse().ok());
+                    Aging {
+                        last_change: day(1),
+                        min_days: day(2),
+                        max_days: day(3),
+                        warn_days: day(4),
+                        inactive_days: day(5),
+                        expire_date: day(6),
+                    }
+                }),
+                name,
+            })
+        })
+        .collect()
+}
+
+fn password_status(field: &str) -> Password {
+    let hash = field.trim_start_matches(['!', '*']);
+    let status = if field.is_empty() {
+        "empty"
+    } else if hash.len() != field.len() {
+        "locked"
+    } else {
+        "hashed"
+    };
+    let algorithm = if let Some(rest) = hash.strip_prefix('$') {
+        Some(match rest.split('$').next().unwrap_or_default() {
+            "1" => "md5",
+            "2a" | "2b" | "2y" => "bcrypt",
+            "5" => "sha256",
+            "6" => "sha512",
+            "7" => "scrypt",
+            "y" => "yescrypt",
+            "gy" => "gost-yescrypt",
+            _ => "unknown",
+        })
+    } else if hash.len() == 13 {
+        Some("des")
+    } else {
+        None
+    };
+    Password {
+        status: status.to_string(),
+        algorithm: algorithm.map(|a| a.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accounts_password_status() {
+        assert_eq!(password_status("").status, "empty");
+        assert_eq!(password_status("!!").status, "locked");
+        assert_eq!(password_status("*").algorithm, None);
+        assert_eq!(
+            password_status("!$6$salt$hash"),
+            Password {
+                status: "locked".to_string(),
+                algorithm: Some("sha512".to_string()),
+            }
+        );
+        assert_eq!(
+            password_status("$y$j9T$salt$hash").algorithm,
+            Some("yescrypt".to_string())
+        );
+    }
+
+    #[test]
+    fn accounts_join() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                      alice:x:1000:1000:Alice:/home/alice:/bin/zsh\n";
+        let group = "root:x:0:\n\
+                     wheel:x:10:alice\n\
+                     alice:x:1000:\n";
+        let shadow = "root:!:19800:0:99999:7:::\n\
+                      alice:$6$salt$hash:19810:1:90:7:30::\n";
+
+        let groups = parse_group(group, &HashMap::new());
+        let users = parse_passwd(passwd, &parse_shadow(shadow), &groups);
+
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[0].password.as_ref().unwrap().status, "locked");
+        assert_eq!(
+            users[1],
+            UserRecord {
+                name: "alice".to_string(),
+                uid: 1000,
+                gid: 1000,
+                group: Some("alice".to_string()),
+                groups: vec!["wheel".to_string()],
+                gecos: "Alice".to_string(),
+                home: "/home/alice".to_string(),
+                shell: "/bin/zsh".to_string(),
+                password: Some(Password {
+                    status: "hashed".to_string(),
+                    algorithm: Some("sha512".to_string()),
+                }),
+                aging: Some(Aging {
+                    last_change: Some(19810),
+                    min_days: Some(1),
+                    max_days: Some(90),
+                    warn_days: Some(7),
+                    inactive_days: Some(30),
+                    expire_date: None,
+                }),
+            }
+        );
+        assert_eq!(groups[1].members, vec!["alice".to_string()]);
+        assert_eq!(groups[1].password, None);
+    }
+}
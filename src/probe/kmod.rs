@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::probe::Runable;
+use crate::runner::Runner;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct KmodProbe {
+    pub modules: Option<Vec<String>>,
+    #[serde(default = "default_config_paths")]
+    pub config_paths: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct ModuleRecord {
+    pub name: String,
+    pub loaded: bool,
+    pub refcount: Option<u32>,
+    pub used_by: Vec<String>,
+    pub blacklisted: bool,
+    pub install: Option<String>,
+    pub install_disabled: bool,
+}
+
+fn default_config_paths() -> Vec<String> {
+    vec!["/etc/modprobe.d/*.conf".to_string()]
+}
+
+/// Module names are interchangeable with dashes and underscores, see modprobe.d(5).
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+impl Runable for KmodProbe {
+    fn run(&self, runner: &mut Runner) -> Vec<(String, String)> {
+        let mut records: BTreeMap<String, ModuleRecord> = BTreeMap::new();
+        if let Some(modules) = &self.modules {
+            for name in modules {
+                let name = normalize(name);
+                records.insert(
+                    name.clone(),
+                    ModuleRecord {
+                        name,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
+        parse_proc_modules(&runner.cat("/proc/modules"), &mut records);
+        let mut configs: Vec<String> = self
+            .config_paths
+            .iter()
+            .flat_map(|p| runner.unglob_path(p))
+            .collect();
+        configs.sort();
+        for config in configs {
+            parse_modprobe_conf(&runner.cat(&config), &mut records);
+        }
+
+        if let Some(modules) = &self.modules {
+            let modules: Vec<String> = modules.iter().map(|m| normalize(m)).collect();
+            records.retain(|name, _| modules.contains(name));
+        }
+        let records: Vec<&ModuleRecord> = records.values().collect();
+        vec![(
+            "/proc/modules".to_string(),
+            serde_json::to_string(&records).expect("Can't serialize modules!"),
+        )]
+    }
+}
+
+fn entry<'a>(records: &'a mut BTreeMap<String, ModuleRecord>, name: &str) -> &'a mut ModuleRecord {
+    let name = normalize(name);
+    records.entry(name.clone()).or_insert_with(|| ModuleRecord {
+        name,
+        ..Default::default()
+    })
+}
+
+fn parse_proc_modules(content: &str, records: &mut BTreeMap<String, ModuleRecord>) {
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 {
+            continue;
+        }
+        let record = entry(records, fields[0]);
+        record.loaded = true;
+        record.refcount = fields[2].parse().ok();
+        record.used_by = fields[3]
+            .split(',')
+            .filter(|m| !m.is_empty() && *m != "-")
+            .map(|m| m.to_string())
+            .collect();
+    }
+}
+
+fn parse_modprobe_conf(content: &str, records: &mut BTreeMap<String, ModuleRecord>) {
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("blacklist"), Some(name)) => entry(records, name).blacklisted = true,
+            (Some("install"), Some(name)) => {
+                let record = entry(records, name);
+                // The first install command wins, later ones are ignored by modprobe.
+                if record.install.is_none() {
+                    let command: Vec<&str> = words.collect();
+                    record.install_disabled = matches!(
+                        command
+                            .first()
+                            .map(|c| c.rsplit('/').next().unwrap_or_default()),
+                        Some("false") | Some("true")
+                    );
+                    record.install = Some(command.join(" "));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmod_merge() {
+        let mut records = BTreeMap::new();
+        parse_proc_modules(
+            "usb_storage 86016 1 uas, Live 0x0000000000000000\n\
+             uas 32768 0 - Live 0x0000000000000000\n",
+            &mut records,
+        );
+        parse_modprobe_conf(
+            "# Disable cramfs\n\
+             install cramfs /bin/false\n\
+             blacklist cramfs\n\
+             install usb-storage /bin/true\n\
+             install usb-storage /sbin/modprobe --ignore-install usb-storage\n",
+            &mut records,
+        );
+
+        assert_eq!(
+            records["cramfs"],
+            ModuleRecord {
+                name: "cramfs".to_string(),
+                blacklisted: true,
+                install: Some("/bin/false".to_string()),
+                install_disabled: true,
+                ..Default::default()
+            }
+        );
+        assert!(records["usb_storage"].loaded);
+        assert!(records["usb_storage"].install_disabled);
+        assert_eq!(records["usb_storage"].used_by, vec!["uas".to_string()]);
+        assert_eq!(records["uas"].refcount, Some(0));
+        assert!(records["uas"].used_by.is_empty());
+    }
+}
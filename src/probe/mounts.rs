@@ -0,0 +1,206 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::probe::Runable;
+use crate::runner::Runner;
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+const FSTAB: &str = "/etc/fstab";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct MountsProbe {
+    pub mount_points: Option<Vec<String>>,
+    #[serde(default)]
+    pub fstab: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct MountRecord {
+    pub mount_id: u32,
+    pub parent_id: u32,
+    pub device: String,
+    pub root: String,
+    pub mount_point: String,
+    pub options: BTreeSet<String>,
+    pub propagation: Vec<String>,
+    pub fs_type: String,
+    pub source: String,
+    pub super_options: BTreeSet<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct FstabRecord {
+    pub source: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub options: BTreeSet<String>,
+    pub freq: u32,
+    pub passno: u32,
+}
+
+impl MountsProbe {
+    fn matches(&self, mount_point: &str) -> bool {
+        self.mount_points
+            .as_ref()
+            .is_none_or(|m| m.iter().any(|m| m == mount_point))
+    }
+}
+
+impl Runable for MountsProbe {
+    fn run(&self, runner: &mut Runner) -> Vec<(String, String)> {
+        let mut mounts = parse_mountinfo(&runner.cat(MOUNTINFO));
+        mounts.retain(|m| self.matches(&m.mount_point));
+        let mut outputs = vec![(
+            MOUNTINFO.to_string(),
+            serde_json::to_string(&mounts).expect("Can't serialize mounts!"),
+        )];
+        if self.fstab {
+            let mut entries = parse_fstab(&runner.cat(FSTAB));
+            entries.retain(|m| self.matches(&m.mount_point));
+            outputs.push((
+                FSTAB.to_string(),
+                serde_json::to_string(&entries).expect("Can't serialize fstab!"),
+            ));
+        }
+        outputs
+    }
+}
+
+/// Decodes the octal escapes (`\040` for a space, etc.) used in mountinfo and fstab fields,
+/// the escaped bytes of a multibyte character are decoded together.
+fn unescape(field: &str) -> String {
+    let mut result = Vec::new();
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        result.extend_from_slice(&rest.as_bytes()[..pos]);
+        let code = rest
+            .get(pos + 1..pos + 4)
+            .and_then(|c| u8::from_str_radix(c, 8).ok());
+        if let Some(code) = code {
+            result.push(code);
+            rest = &rest[pos + 4..];
+        } else {
+            result.push(b'\\');
+            rest = &rest[pos + 1..];
+        }
+    }
+    result.extend_from_slice(rest.as_bytes());
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+fn options(field: &str) -> BTreeSet<String> {
+    field
+        .split(',')
+        .filter(|o| !o.is_empty())
+        .map(unescape)
+        .collect()
+}
+
+fn parse_mountinfo(content: &str) -> Vec<MountRecord> {
+    let mut records = Vec::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // Optional fields are terminated by a single hyphen.
+        let Some(sep) = fields.iter().position(|f| *f == "-") else {
+            continue;
+        };
+        if sep < 6 || fields.len() < sep + 4 {
+            continue;
+        }
+        let (Ok(mount_id), Ok(parent_id)) = (fields[0].parse(), fields[1].parse()) else {
+            continue;
+        };
+        records.push(MountRecord {
+            mount_id,
+            parent_id,
+            device: fields[2].to_string(),
+            root: unescape(fields[3]),
+            mount_point: unescape(fields[4]),
+            options: options(fields[5]),
+            propagation: fields[6..sep].iter().map(|f| f.to_string()).collect(),
+            fs_type: fields[sep + 1].to_string(),
+            source: unescape(fields[sep + 2]),
+            super_options: options(fields[sep + 3]),
+        });
+    }
+    records
+}
+
+fn parse_fstab(content: &str) -> Vec<FstabRecord> {
+    let mut records = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 {
+            continue;
+        }
+        records.push(FstabRecord {
+            source: unescape(fields[0]),
+            mount_point: unescape(fields[1]),
+            fs_type: fields[2].to_string(),
+            options: options(fields.get(3).unwrap_or(&"defaults")),
+            freq: fields
+                .get(4)
+                .and_then(|f| f.parse().ok())
+                .unwrap_or_default(),
+            passno: fields
+                .get(5)
+                .and_then(|f| f.parse().ok())
+                .unwrap_or_default(),
+        });
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mounts_parse_mountinfo() {
+        let content = "23 28 0:22 / /proc rw,relatime shared:13 - proc proc rw\n\
+                       61 28 0:35 / /tmp rw,nosuid,nodev,noexec - tmpfs tmpfs rw,size=4096k,mode=1777\n\
+                       70 28 8:1 /data /mnt/my\\040disk ro master:1 shared:2 - ext4 /dev/sda1 rw\n\
+                       71 28 8:2 / /mnt/caf\\303\\251\\134x ro - ext4 /dev/sda2 rw\n";
+
+        let records = parse_mountinfo(content);
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].propagation, vec!["shared:13".to_string()]);
+        assert_eq!(
+            records[1],
+            MountRecord {
+                mount_id: 61,
+                parent_id: 28,
+                device: "0:35".to_string(),
+                root: "/".to_string(),
+                mount_point: "/tmp".to_string(),
+                options: options("noexec,nodev,nosuid,rw"),
+                propagation: vec![],
+                fs_type: "tmpfs".to_string(),
+                source: "tmpfs".to_string(),
+                super_options: options("rw,size=4096k,mode=1777"),
+            }
+        );
+        assert_eq!(records[2].mount_point, "/mnt/my disk");
+        assert_eq!(records[2].propagation.len(), 2);
+        assert_eq!(records[3].mount_point, "/mnt/caf\u{e9}\\x");
+    }
+
+    #[test]
+    fn mounts_parse_fstab() {
+        let content = "# /etc/fstab\n\
+                       UUID=cb60f854 / btrfs subvol=root,compress=zstd:1 0 0\n\
+                       tmpfs /tmp tmpfs nodev,nosuid\n";
+
+        let records = parse_fstab(content);
+        assert_eq!(records.len(), 2);
+        assert!(records[0].options.contains("compress=zstd:1"));
+        assert_eq!(records[1].mount_point, "/tmp");
+        assert_eq!(records[1].passno, 0);
+        assert!(!records[1].options.contains("noexec"));
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::probe::Runable;
+use crate::runner::Runner;
+use crate::wrapper::parse_env;
+
+const OS_RELEASE_PATHS: [&str; 2] = ["/etc/os-release", "/usr/lib/os-release"];
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PlatformProbe {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct PlatformRecord {
+    pub family: String,
+    pub id: String,
+    pub id_like: Vec<String>,
+    pub name: String,
+    pub version_id: String,
+    pub pretty_name: String,
+    pub sysname: String,
+    pub kernel_release: String,
+    pub machine: String,
+    pub virtualization: String,
+    pub container: String,
+    pub cpe: String,
+}
+
+impl Runable for PlatformProbe {
+    fn run(&self, runner: &mut Runner) -> Vec<(String, String)> {
+        let mut record = PlatformRecord::default();
+
+        // Keyed by the os-release file which was read, the first one when there is none.
+        let mut source = OS_RELEASE_PATHS[0];
+        for path in OS_RELEASE_PATHS {
+            let content = runner.cat(path);
+            if !content.is_empty() {
+                parse_os_release(&content, &mut record);
+                source = path;
+                break;
+            }
+        }
+
+        let uname = runner.run(
+            "/usr/bin/uname",
+            &vec!["-s".to_string(), "-r".to_string(), "-m".to_string()],
+        );
+        let mut uname = uname.split_whitespace().map(|f| f.to_string());
+        record.sysname = uname.next().unwrap_or_default();
+        record.kernel_release = uname.next().unwrap_or_default();
+        record.machine = uname.next().unwrap_or_default();
+        // OVAL 'affected family' of anything running a Linux kernel.
+        record.family = match record.sysname.as_str() {
+            "Linux" | "" => "unix".to_string(),
+            other => other.to_lowercase(),
+        };
+
+        record.container = detect_container(runner);
+        record.virtualization = detect_virtualization(runner);
+
+        vec![(
+            source.to_string(),
+            serde_json::to_string(&record).expect("Can't serialize platform!"),
+        )]
+    }
+}
+
+fn parse_os_release(content: &str, record: &mut PlatformRecord) {
+    let os_release = parse_env(content);
+    let field = |key: &str| {
+        os_release
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    record.id = field("ID");
+    record.id_like = field("ID_LIKE")
+        .split_whitespace()
+        .map(|v| v.to_string())
+        .collect();
+    record.name = field("NAME");
+    record.version_id = field("VERSION_ID");
+    record.pretty_name = field("PRETTY_NAME");
+    // Distributions without CPE_NAME get a best effort one with the id as the vendor.
+    record.cpe = match field("CPE_NAME") {
+        cpe if !cpe.is_empty() => cpe,
+        _ => format!("cpe:/o:{}:{}:{}", record.id, record.id, record.version_id),
+    };
+}
+
+fn detect_container(runner: &mut Runner) -> String {
+    if !runner.unglob_path("/run/.containerenv").is_empty() {
+        return "podman".to_string();
+    }
+    if !runner.unglob_path("/.dockerenv").is_empty() {
+        return "docker".to_string();
+    }
+    let cgroup = runner.cat("/proc/1/cgroup");
+    for (marker, container) in [
+        ("kubepods", "kubernetes"),
+        ("docker", "docker"),
+        ("lxc", "lxc"),
+    ] {
+        if cgroup.contains(marker) {
+            return container.to_string();
+        }
+    }
+    "none".to_string()
+}
+
+fn detect_virtualization(runner: &mut Runner) -> String {
+    let dmi = format!(
+        "{} {}",
+        runner.cat("/sys/class/dmi/id/sys_vendor").trim(),
+        runner.cat("/sys/class/dmi/id/product_name").trim()
+    );
+    let vendors = [
+        ("QEMU", "kvm"),
+        ("KVM", "kvm"),
+        ("VMware", "vmware"),
+        ("VirtualBox", "oracle"),
+        ("innotek", "oracle"),
+        ("Xen", "xen"),
+        ("Amazon EC2", "amazon"),
+        ("Google", "google"),
+        ("Microsoft Corporation Virtual Machine", "microsoft"),
+    ];
+    if let Some((_, virt)) = vendors.iter().find(|(vendor, _)| dmi.contains(vendor)) {
+        return virt.to_string();
+    }
+    let cpuinfo = runner.cat("/proc/cpuinfo");
+    if cpuinfo
+        .lines()
+        .any(|l| l.starts_with("flags") && l.split_whitespace().any(|f| f == "hypervisor"))
+    {
+        "unknown".to_string()
+    } else {
+        "none".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_parse_os_release() {
+        let content = "NAME=\"Fedora Linux\"\n\
+                       VERSION_ID=41\n\
+                       ID=fedora\n\
+                       PRETTY_NAME=\"Fedora Linux 41 (Workstation Edition)\"\n\
+                       CPE_NAME=\"cpe:/o:fedoraproject:fedora:41\"\n";
+
+        let mut record = PlatformRecord::default();
+        parse_os_release(content, &mut record);
+        assert_eq!(record.id, "fedora");
+        assert_eq!(record.version_id, "41");
+        assert_eq!(record.cpe, "cpe:/o:fedoraproject:fedora:41");
+
+        let content = "ID=ubuntu\nID_LIKE=debian\nVERSION_ID=\"24.04\"\n";
+        let mut record = PlatformRecord::default();
+        parse_os_release(content, &mut record);
+        assert_eq!(record.id_like, vec!["debian".to_string()]);
+        assert_eq!(record.cpe, "cpe:/o:ubuntu:ubuntu:24.04");
+    }
+}
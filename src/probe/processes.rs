@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use crate::probe::Runable;
+use crate::runner::Runner;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ProcessesProbe {
+    pub comm: Option<String>,
+    pub exe: Option<String>,
+    /// Effective uid, i.e. the privileges the process runs with.
+    pub uid: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct ProcessRecord {
+    pub pid: u32,
+    pub ppid: u32,
+    pub uid: u32,
+    pub euid: u32,
+    pub gid: u32,
+    pub egid: u32,
+    pub comm: String,
+    pub exe: Option<String>,
+    pub cmdline: Vec<String>,
+    pub cgroup: Vec<String>,
+}
+
+impl ProcessesProbe {
+    /// Filters on the fields of the status file, so that other pids cost a single read.
+    fn matches_status(&self, record: &ProcessRecord) -> bool {
+        self.comm.as_ref().is_none_or(|comm| &record.comm == comm)
+            && self.uid.is_none_or(|uid| record.euid == uid)
+    }
+
+    fn matches_exe(&self, record: &ProcessRecord) -> bool {
+        self.exe
+            .as_ref()
+            .is_none_or(|exe| record.exe.as_ref() == Some(exe))
+    }
+}
+
+impl Runable for ProcessesProbe {
+    fn run(&self, runner: &mut Runner) -> Vec<(String, String)> {
+        let mut records = Vec::new();
+        for dir in runner.unglob_path("/proc/[0-9]*") {
+            let status = runner.cat(&format!("{}/status", dir));
+            // The process is gone or not accessible.
+            let Some(mut record) = parse_status(&status) else {
+                continue;
+            };
+            if !self.matches_status(&record) {
+                continue;
+            }
+            let exe = runner.run("/usr/bin/readlink", &vec![format!("{}/exe", dir)]);
+            record.exe = Some(exe.trim_end().to_string()).filter(|e| !e.is_empty());
+            if !self.matches_exe(&record) {
+                continue;
+            }
+            record.cmdline = runner
+                .cat(&format!("{}/cmdline", dir))
+                .split_terminator('\0')
+                .map(|a| a.to_string())
+                .collect();
+            record.cgroup = runner
+                .cat(&format!("{}/cgroup", dir))
+                .lines()
+                .map(|l| l.to_string())
+                .collect();
+            records.push(record);
+        }
+        vec![(
+            "/proc".to_string(),
+            serde_json::to_string(&records).expect("Can't serialize process table!"),
+        )]
+    }
+}
+
+fn parse_status(status: &str) -> Option<ProcessRecord> {
+    let mut record = ProcessRecord::default();
+    let mut has_pid = false;
+    for line in status.lines() {
+        let Some((key, val)) = line.split_once(':') else {
+            continue;
+        };
+        let val = val.trim();
+        // Uid and Gid lines list real, effective, saved and fs ids.
+        let nth = |n| val.split_whitespace().nth(n).and_then(|v| v.parse().ok());
+        let first = || nth(0);
+        match key {
+            "Name" => record.comm = val.to_string(),
+            "Pid" => {
+                record.pid = first()?;
+                has_pid = true;
+            }
+            "PPid" => record.ppid = first()?,
+            "Uid" => (record.uid, record.euid) = (first()?, nth(1)?),
+            "Gid" => (record.gid, record.egid) = (first()?, nth(1)?),
+            _ => {}
+        }
+    }
+    has_pid.then_some(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn processes_parse_status() {
+        let status = "Name:\tsshd\n\
+                      Umask:\t0022\n\
+                      State:\tS (sleeping)\n\
+                      Tgid:\t1042\n\
+                      Pid:\t1042\n\
+                      PPid:\t1\n\
+                      Uid:\t1000\t0\t0\t0\n\
+                      Gid:\t74\t74\t74\t74\n";
+
+        assert_eq!(
+            parse_status(status),
+            Some(ProcessRecord {
+                pid: 1042,
+                ppid: 1,
+                uid: 1000,
+                euid: 0,
+                gid: 74,
+                egid: 74,
+                comm: "sshd".to_string(),
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_status(""), None);
+    }
+
+    #[test]
+    fn processes_filter() {
+        let probe = ProcessesProbe {
+            comm: Some("sshd".to_string()),
+            exe: Some("/usr/sbin/sshd".to_string()),
+            uid: Some(0),
+        };
+        let record = ProcessRecord {
+            comm: "sshd".to_string(),
+            uid: 1000,
+            ..Default::default()
+        };
+
+        assert!(probe.matches_status(&record));
+        assert!(!probe.matches_status(&ProcessRecord {
+            euid: 1000,
+            ..Default::default()
+        }));
+        assert!(!probe.matches_exe(&record));
+    }
+}
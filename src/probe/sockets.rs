@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::probe::Runable;
+use crate::runner::Runner;
+
+// Kernel socket flag marking a unix socket that accepts connections.
+const SO_ACCEPTCON: u32 = 0x10000;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SocketsProbe {
+    #[serde(default = "default_protocols")]
+    pub protocols: Vec<Protocol>,
+    #[serde(default)]
+    pub listening: bool,
+    #[serde(default)]
+    pub processes: bool,
+}
+
+/// Socket table, read from `/proc/net/<protocol>`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Tcp6,
+    Udp,
+    Udp6,
+    Unix,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct SocketRecord {
+    pub protocol: String,
+    pub local_address: String,
+    pub local_port: Option<u16>,
+    pub remote_address: Option<String>,
+    pub remote_port: Option<u16>,
+    pub state: String,
+    pub listening: bool,
+    pub inode: u64,
+    pub uid: Option<u32>,
+    pub pids: Option<Vec<u32>>,
+}
+
+fn default_protocols() -> Vec<Protocol> {
+    vec![
+        Protocol::Tcp,
+        Protocol::Tcp6,
+        Protocol::Udp,
+        Protocol::Udp6,
+        Protocol::Unix,
+    ]
+}
+
+impl Protocol {
+    fn name(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Tcp6 => "tcp6",
+            Protocol::Udp => "udp",
+            Protocol::Udp6 => "udp6",
+            Protocol::Unix => "unix",
+        }
+    }
+}
+
+impl Runable for SocketsProbe {
+    fn run(&self, runner: &mut Runner) -> Vec<(String, String)> {
+        let owners = if self.processes {
+            Some(socket_owners(runner))
+        } else {
+            None
+        };
+        let mut outputs = Vec::new();
+        for protocol in &self.protocols {
+            let path = format!("/proc/net/{}", protocol.name());
+            let table = runner.cat(&path);
+            let mut records = match protocol {
+                Protocol::Unix => parse_unix(&table),
+                protocol => parse_inet(protocol.name(), &table),
+            };
+            records.retain(|r| !self.listening || r.listening);
+            if let Some(owners) = &owners {
+                for record in records.iter_mut() {
+                    record.pids = Some(owners.get(&record.inode).cloned().unwrap_or_default());
+                }
+            }
+            outputs.push((
+                path,
+                serde_json::to_string(&records).expect("Can't serialize socket table!"),
+            ));
+        }
+        outputs
+    }
+}
+
+/// Maps socket inodes to the pids holding them open, using the `socket:[inode]` fd links.
+fn socket_owners(runner: &mut Runner) -> HashMap<u64, Vec<u32>> {
+    let args = [
+        "/proc",
+        "-mindepth",
+        "3",
+        "-maxdepth",
+        "3",
+        "-path",
+        "/proc/[0-9]*/fd/*",
+        "-lname",
+        "socket:*",
+        "-printf",
+        "%p %l\\n",
+    ];
+    let output = runner.run(
+        "/usr/bin/find",
+        &args.iter().map(|a| a.to_string()).collect(),
+    );
+    let mut owners: HashMap<u64, Vec<u32>> = HashMap::new();
+    for line in output.lines() {
+        let Some((fd, link)) = line.split_once(' ') else {
+            continue;
+        };
+        let pid = fd.split('/').nth(2).and_then(|p| p.parse().ok());
+        let inode = link
+            .strip_prefix("socket:[")
+            .and_then(|l| l.strip_suffix(']'))
+            .and_then(|i| i.parse().ok());
+        if let (Some(pid), Some(inode)) = (pid, inode) {
+            let pids = owners.entry(inode).or_default();
+            if !pids.contains(&pid) {
+                pids.push(pid);
+            }
+        }
+    }
+    owners
+}
+
+fn parse_inet(protocol: &str, table: &str) -> Vec<SocketRecord> {
+    let mut records = Vec::new();
+    for line in table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            continue;
+        }
+        let (Some((local_address, local_port)), Some((remote_address, remote_port))) =
+            (parse_endpoint(fields[1]), parse_endpoint(fields[2]))
+        else {
+            continue;
+        };
+        let state = u8::from_str_radix(fields[3], 16).unwrap_or_default();
+        let is_tcp = protocol.starts_with("tcp");
+        records.push(SocketRecord {
+            protocol: protocol.to_string(),
+            local_address,
+            local_port: Some(local_port),
+            remote_address: Some(remote_address),
+            remote_port: Some(remote_port),
+            state: tcp_state_name(state).to_string(),
+            // UDP sockets have no LISTEN state, an unconnected bound socket receives instead.
+            listening: if is_tcp { state == 0x0a } else { state == 0x07 },
+            inode: fields[9].parse().unwrap_or_default(),
+            uid: fields[7].parse().ok(),
+            pids: None,
+        });
+    }
+    records
+}
+
+fn parse_unix(table: &str) -> Vec<SocketRecord> {
+    let mut records = Vec::new();
+    for line in table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 7 {
+            continue;
+        }
+        let flags = u32::from_str_radix(fields[3], 16).unwrap_or_default();
+        let state = match fields[5] {
+            "01" => "UNCONNECTED",
+            "02" => "CONNECTING",
+            "03" => "CONNECTED",
+            "04" => "DISCONNECTING",
+            _ => "UNKNOWN",
+        };
+        records.push(SocketRecord {
+            protocol: "unix".to_string(),
+            local_address: fields[7..].join(" "),
+            state: state.to_string(),
+            listening: flags & SO_ACCEPTCON != 0,
+            inode: fields[6].parse().unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+    records
+}
+
+/// Decodes `ADDR:PORT` where the address is hex in host (little-endian) byte order per 32-bit word.
+fn parse_endpoint(endpoint: &str) -> Option<(String, u16)> {
+    let (addr, port) = endpoint.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let words = (0..addr.len())
+        .step_by(8)
+        .map(|i| {
+            addr.get(i..i + 8)
+                .and_then(|w| u32::from_str_radix(w, 16).ok())
+        })
+        .collect::<Option<Vec<u32>>>()?;
+    let addr = match words.as_slice() {
+        [w] => Ipv4Addr::from(w.to_le_bytes()).to_string(),
+        [_, _, _, _] => {
+            let mut octets = [0u8; 16];
+            for (i, w) in words.iter().enumerate() {
+                octets[i * 4..i * 4 + 4].copy_from_slice(&w.to_le_bytes());
+            }
+            Ipv6Addr::from(octets).to_string()
+        }
+        _ => return None,
+    };
+    Some((addr, port))
+}
+
+fn tcp_state_name(state: u8) -> &'static str {
+    match state {
+        0x01 => "ESTABLISHED",
+        0x02 => "SYN_SENT",
+        0x03 => "SYN_RECV",
+        0x04 => "FIN_WAIT1",
+        0x05 => "FIN_WAIT2",
+        0x06 => "TIME_WAIT",
+        0x07 => "CLOSE",
+        0x08 => "CLOSE_WAIT",
+        0x09 => "LAST_ACK",
+        0x0a => "LISTEN",
+        0x0b => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sockets_protocols() {
+        let probe: SocketsProbe =
+            serde_json::from_str(r#"{"protocols": ["tcp6", "unix"]}"#).unwrap();
+        assert_eq!(probe.protocols, vec![Protocol::Tcp6, Protocol::Unix]);
+        assert_eq!(
+            serde_json::from_str::<SocketsProbe>("{}")
+                .unwrap()
+                .protocols
+                .len(),
+            5
+        );
+        assert!(serde_json::from_str::<SocketsProbe>(r#"{"protocols": ["sctp"]}"#).is_err());
+    }
+
+    #[test]
+    fn sockets_parse_endpoint() {
+        assert_eq!(
+            parse_endpoint("0100007F:0277"),
+            Some(("127.0.0.1".to_string(), 631))
+        );
+        assert_eq!(
+            parse_endpoint("00000000000000000000000001000000:0016"),
+            Some(("::1".to_string(), 22))
+        );
+        assert_eq!(parse_endpoint("garbage"), None);
+    }
+
+    #[test]
+    fn sockets_parse_inet() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+                     \x20  0: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 21035 1 0000000000000000 100 0 0 10 0\n\
+                     \x20  1: 0F02000A:0016 0102000A:D5B2 01 00000000:00000000 02:0009E8A6 00000000     0        0 48811 4 0000000000000000 20 4 31 10 -1\n";
+
+        let records = parse_inet("tcp", table);
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0],
+            SocketRecord {
+                protocol: "tcp".to_string(),
+                local_address: "0.0.0.0".to_string(),
+                local_port: Some(22),
+                remote_address: Some("0.0.0.0".to_string()),
+                remote_port: Some(0),
+                state: "LISTEN".to_string(),
+                listening: true,
+                inode: 21035,
+                uid: Some(0),
+                pids: None,
+            }
+        );
+        assert_eq!(records[1].state, "ESTABLISHED");
+        assert_eq!(records[1].remote_address, Some("10.0.2.1".to_string()));
+        assert!(!records[1].listening);
+    }
+
+    #[test]
+    fn sockets_parse_unix() {
+        let table = "Num       RefCount Protocol Flags    Type St Inode Path\n\
+                     0000000000000000: 00000002 00000000 00010000 0001 01 17330 /run/systemd/private\n\
+                     0000000000000000: 00000003 00000000 00000000 0001 03 22087\n";
+
+        let records = parse_unix(table);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].local_address, "/run/systemd/private");
+        assert!(records[0].listening);
+        assert_eq!(records[1].state, "CONNECTED");
+        assert_eq!(records[1].inode, 22087);
+    }
+}
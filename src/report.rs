@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::time::Instant;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::block::Outcome;
+use crate::error;
+use crate::trace::Trace;
+
+pub mod junit;
+
+/// Version of the result document, bumped on incompatible changes of its schema,
+/// `schema/report.schema.json`.
+pub const REPORT_VERSION: &str = "1.0";
+
+/// Result document of a run.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Report {
+    pub version: String,
+    pub run: Run,
+    pub tests: Vec<TestReport>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Run {
+    pub prospector_version: String,
+    pub target: String,
+    pub started: String,
+    /// Set by `finish`, a run which could not load its tests is reported without it.
+    pub finished: Option<String>,
+    pub content: Vec<Content>,
+}
+
+/// Test file the run loaded, identified by the hash of its content.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Content {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TestReport {
+    pub id: String,
+    pub verdict: Verdict,
+    pub result: Value,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub evidence: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub started: String,
+    pub wall_time_ms: f64,
+    /// Kept for the report formats embedding it, the JSON document doesn't.
+    #[serde(skip)]
+    pub trace: Option<Trace>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Pass,
+    Fail,
+    NotApplicable,
+    Error,
+}
+
+/// Output format of the report, `json` for the result document, `junit` for JUnit XML.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Json,
+    Junit,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "junit" => Ok(Format::Junit),
+            _ => Err(format!(
+                "Unknown format '{}', expected 'json' or 'junit'!",
+                s
+            )),
+        }
+    }
+}
+
+fn now() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .expect("Can't format the current time!")
+}
+
+impl Content {
+    pub fn new(path: &str, content: &str) -> Self {
+        Content {
+            path: path.to_string(),
+            sha256: format!("{:x}", Sha256::digest(content.as_bytes())),
+        }
+    }
+}
+
+impl Report {
+    pub fn new(target: &Option<String>, content: Vec<Content>) -> Self {
+        Report {
+            version: REPORT_VERSION.to_string(),
+            run: Run {
+                prospector_version: env!("CARGO_PKG_VERSION").to_string(),
+                target: target.clone().unwrap_or_else(|| "local://".to_string()),
+                started: now(),
+                finished: None,
+                content,
+            },
+            tests: Vec::new(),
+        }
+    }
+
+    /// Runs and records a test.
+    pub fn add_test(
+        &mut self,
+        id: &str,
+        execute: impl FnOnce() -> error::Result<Outcome>,
+    ) -> &mut TestReport {
+        let started = now();
+        let start = Instant::now();
+        let result = execute();
+        let wall_time_ms = start.elapsed().as_secs_f64() * 1000.0;
+        self.tests
+            .push(TestReport::new(id, result, started, wall_time_ms));
+        self.tests.last_mut().expect("A test was just added.")
+    }
+
+    pub fn finish(&mut self) {
+        self.run.finished = Some(now());
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Json => serde_json::to_string_pretty(self).unwrap(),
+            Format::Junit => junit::render(self),
+        }
+    }
+}
+
+impl TestReport {
+    pub fn new(
+        id: &str,
+        result: error::Result<Outcome>,
+        started: String,
+        wall_time_ms: f64,
+    ) -> Self {
+        let (verdict, result, evidence, errors) = match result {
+            Ok(outcome) => {
+                let (verdict, errors) = match &outcome.result {
+                    _ if !outcome.applicable => (Verdict::NotApplicable, vec![]),
+                    Value::Bool(true) => (Verdict::Pass, vec![]),
+                    Value::Bool(false) => (Verdict::Fail, vec![]),
+                    value => (
+                        Verdict::Error,
+                        vec![format!("A boolean result is expected, got '{}'!", value)],
+                    ),
+                };
+                (verdict, outcome.result, outcome.evidence, errors)
+            }
+            Err(e) => (Verdict::Error, Value::Null, Map::new(), vec![e.to_string()]),
+        };
+        TestReport {
+            id: id.to_string(),
+            verdict,
+            result,
+            evidence,
+            errors,
+            started,
+            wall_time_ms,
+            trace: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(result: Value) -> error::Result<Outcome> {
+        Ok(Outcome {
+            applicable: true,
+            result,
+            evidence: Map::new(),
+        })
+    }
+
+    #[test]
+    fn test_report_verdict() {
+        let verdict = |r| TestReport::new("t", r, now(), 0.0).verdict;
+        assert_eq!(verdict(outcome(true.into())), Verdict::Pass);
+        assert_eq!(verdict(outcome(false.into())), Verdict::Fail);
+        assert_eq!(
+            verdict(Ok(Outcome {
+                applicable: false,
+                result: Value::Null,
+                evidence: Map::new(),
+            })),
+            Verdict::NotApplicable
+        );
+        // A probe value is not mistaken for the verdict.
+        assert_eq!(verdict(outcome("notapplicable".into())), Verdict::Error);
+        assert_eq!(verdict(outcome(2.into())), Verdict::Error);
+        assert_eq!(
+            verdict(Err(error::Error::Filter("boom".to_string()))),
+            Verdict::Error
+        );
+    }
+
+    #[test]
+    fn content_hash() {
+        assert_eq!(
+            Content::new("empty.json", "").sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::report::{Report, TestReport, Verdict};
+
+/// Escapes text for XML content, dropping the characters XML 1.0 can't hold.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes text for a double quoted XML attribute.
+fn escape_attribute(text: &str) -> String {
+    escape(text).replace('"', "&quot;")
+}
+
+fn seconds(wall_time_ms: f64) -> String {
+    format!("{:.3}", wall_time_ms / 1000.0)
+}
+
+fn count(report: &Report, verdict: Verdict) -> usize {
+    report.tests.iter().filter(|t| t.verdict == verdict).count()
+}
+
+/// Body of a failure or error: the errors followed by the evidence.
+fn details(test: &TestReport) -> String {
+    let mut details = test.errors.join("\n");
+    if !test.evidence.is_empty() {
+        if !details.is_empty() {
+            details.push('\n');
+        }
+        details.push_str("Evidence: ");
+        details.push_str(&serde_json::to_string_pretty(&test.evidence).unwrap());
+    }
+    details
+}
+
+fn testcase(xml: &mut String, test: &TestReport) {
+    xml.push_str(&format!(
+        "    <testcase name=\"{}\" classname=\"prospector\" time=\"{}\"",
+        escape_attribute(&test.id),
+        seconds(test.wall_time_ms)
+    ));
+    let mut children = String::new();
+    match test.verdict {
+        Verdict::Pass => {}
+        Verdict::Fail => children.push_str(&format!(
+            "      <failure message=\"{}\" type=\"fail\">{}</failure>\n",
+            escape_attribute(&format!("The result is {}", test.result)),
+            escape(&details(test))
+        )),
+        Verdict::Error => children.push_str(&format!(
+            "      <error message=\"{}\" type=\"error\">{}</error>\n",
+            escape_attribute(test.errors.first().map_or("", |e| e.as_str())),
+            escape(&details(test))
+        )),
+        Verdict::NotApplicable => children.push_str("      <skipped message=\"notapplicable\"/>\n"),
+    }
+    if let Some(trace) = &test.trace {
+        children.push_str(&format!(
+            "      <system-out>{}</system-out>\n",
+            escape(&serde_json::to_string_pretty(trace).unwrap())
+        ));
+    }
+    if children.is_empty() {
+        xml.push_str("/>\n");
+    } else {
+        xml.push_str(">\n");
+        xml.push_str(&children);
+        xml.push_str("    </testcase>\n");
+    }
+}
+
+/// Renders the report as a JUnit XML document, one testcase per test in a single testsuite.
+pub fn render(report: &Report) -> String {
+    let time = seconds(report.tests.iter().map(|t| t.wall_time_ms).sum());
+    let counts = format!(
+        "tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\"",
+        report.tests.len(),
+        count(report, Verdict::Fail),
+        count(report, Verdict::Error),
+        count(report, Verdict::NotApplicable),
+        time
+    );
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<testsuites name=\"prospector\" {}>\n", counts));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" {} timestamp=\"{}\">\n",
+        escape_attribute(&report.run.target),
+        counts,
+        escape_attribute(&report.run.started)
+    ));
+    for test in &report.tests {
+        testcase(&mut xml, test);
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_text() {
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>\u{1b}[0m\n"),
+            "&lt;a href=\"x\"&gt;'&amp;'&lt;/a&gt;[0m\n"
+        );
+        assert_eq!(
+            escape_attribute("say \"hi\" & go"),
+            "say &quot;hi&quot; &amp; go"
+        );
+    }
+}
@@ -4,9 +4,13 @@ use simplelog::info;
 use std::{collections::HashMap, path::PathBuf, process};
 use url_parse::core::Parser;
 
+use crate::trace::{Cache, CommandTrace};
+
 pub struct Runner {
     cache: HashMap<String, String>,
     plug: Plug,
+    /// Commands executed since the last `take_trace`, recorded only when tracing.
+    trace: Option<Vec<CommandTrace>>,
 }
 
 pub enum Plug {
@@ -34,14 +38,14 @@ impl Fixture {
 }
 
 trait Plugged {
-    fn run(&self, cmd: &str, args: &Vec<String>) -> Vec<u8>;
+    fn run(&self, cmd: &str, args: &[String]) -> Vec<u8>;
     fn has_cache(&self) -> bool {
-        return false;
+        false
     }
 }
 
 impl Plugged for Plug {
-    fn run(&self, cmd: &str, args: &Vec<String>) -> Vec<u8> {
+    fn run(&self, cmd: &str, args: &[String]) -> Vec<u8> {
         match self {
             Plug::Local(_local) => {
                 let p = process::Command::new(cmd)
@@ -56,7 +60,9 @@ impl Plugged for Plug {
                     .fixture
                     .src
                     .get(&key)
-                    .expect(format!("Result for '{}' is not defined in the fixture!", key).as_str())
+                    .unwrap_or_else(|| {
+                        panic!("Result for '{}' is not defined in the fixture!", key)
+                    })
                     .clone()
                     .into()
             }
@@ -64,19 +70,26 @@ impl Plugged for Plug {
     }
 }
 
-fn generate_key(cmd: &str, args: &Vec<String>) -> String {
+fn generate_key(cmd: &str, args: &[String]) -> String {
     let mut key = String::new();
     key.push_str(cmd);
-    key.push_str(" ");
+    key.push(' ');
     key.push_str(&args.join(" "));
     key
 }
 
+impl Default for Runner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Runner {
     pub fn new() -> Self {
         Runner {
             cache: Default::default(),
             plug: Plug::Local(LocalPlug {}),
+            trace: None,
         }
     }
 
@@ -105,6 +118,25 @@ impl Runner {
         Runner {
             cache: Default::default(),
             plug,
+            trace: None,
+        }
+    }
+
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.trace = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    /// Returns the commands executed since the previous call.
+    pub fn take_trace(&mut self) -> Vec<CommandTrace> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn record(&mut self, command: &str, cache: Cache) {
+        if let Some(trace) = &mut self.trace {
+            trace.push(CommandTrace {
+                command: command.to_string(),
+                cache,
+            });
         }
     }
 
@@ -123,8 +155,8 @@ impl Runner {
     }
 
     pub fn unglob_path(&mut self, path: &str) -> Vec<String> {
-        // TODO: Use shellescape
-        let script = format!("compgen -G '{}'", path);
+        // A quote can't be escaped within single quotes, it closes them and is escaped outside.
+        let script = format!("compgen -G '{}'", path.replace('\'', r"'\''"));
         self.sh(&script)
     }
 
@@ -141,9 +173,11 @@ impl Runner {
         info!("Executing command: {} {:?}", cmd, args);
         let key = generate_key(cmd, args);
 
-        if let Some(output) = self.get_value_from_cache(&key) {
-            output.clone()
+        if let Some(output) = self.get_value_from_cache(&key).cloned() {
+            self.record(&key, Cache::Hit);
+            output
         } else {
+            self.record(&key, Cache::Miss);
             let raw_output = self.plug.run(cmd, args);
             let output = String::from_utf8(raw_output).expect("Can't decode output!");
             self.add_value_to_cache(&key, output.clone());
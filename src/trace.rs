@@ -0,0 +1,96 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Raw outputs longer than this are truncated in the trace.
+pub const MAX_RAW_OUTPUT: usize = 4096;
+
+/// Record of a test execution, one entry per executed block in execution order.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Trace {
+    pub test: String,
+    pub blocks: Vec<BlockTrace>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct BlockTrace {
+    pub id: String,
+    /// Commands the probe executed through the runner.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<CommandTrace>,
+    /// Probe outputs by source (path or command) before wrapping.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub raw: Vec<RawOutput>,
+    /// Results of the blocks listed in `src`, as seen by a filter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Map<String, Value>>,
+    /// Wrapped value of a probe, result of a filter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub wall_time_ms: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CommandTrace {
+    pub command: String,
+    pub cache: Cache,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Cache {
+    Hit,
+    Miss,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RawOutput {
+    pub source: String,
+    pub output: String,
+    /// Size of the whole output in bytes.
+    pub size: usize,
+    pub truncated: bool,
+}
+
+impl Trace {
+    pub fn new(test: &str) -> Self {
+        Trace {
+            test: test.to_string(),
+            blocks: Vec::new(),
+        }
+    }
+}
+
+impl RawOutput {
+    pub fn new(source: &str, output: &str) -> Self {
+        let mut end = output.len().min(MAX_RAW_OUTPUT);
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        RawOutput {
+            source: source.to_string(),
+            output: output[..end].to_string(),
+            size: output.len(),
+            truncated: end < output.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_output_truncated() {
+        let output = "é".repeat(MAX_RAW_OUTPUT);
+        let raw = RawOutput::new("/etc/motd", &output);
+        assert_eq!(raw.size, 2 * MAX_RAW_OUTPUT);
+        assert_eq!(raw.output.len(), MAX_RAW_OUTPUT);
+        assert!(raw.truncated);
+
+        let raw = RawOutput::new("/etc/motd", "hello");
+        assert_eq!(raw.output, "hello");
+        assert!(!raw.truncated);
+    }
+}
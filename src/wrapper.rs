@@ -1,9 +1,18 @@
-use std::{collections::HashSet, io::Cursor};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Cursor,
+};
 
-use regex::RegexBuilder;
-use serde::{Deserialize, Serialize};
+use regex::{Regex, RegexBuilder};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use serde_json::{Map, Value};
 use simplelog::warn;
+use sxd_document::dom::{ChildOfElement, Element};
+use sxd_xpath::nodeset::Node;
+
+use crate::compiled::Compiled;
+use crate::error::{Error, Result};
+use crate::runner::Runner;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "kebab-case")]
@@ -13,13 +22,47 @@ pub enum Wrapper {
     RawLines(RawLines),
     Regexp(Regexp),
     CmdLine(CmdLine),
+    Ini(Ini),
+    Yaml(Yaml),
+    Toml(Toml),
+    Xml(Xml),
+    Columns(Columns),
+    EnvFile(EnvFile),
+    Keyword(Keyword),
+}
+
+/// Either a single wrapper or a non-empty list of stages, where each stage wraps the value
+/// produced by the previous one.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Pipeline {
+    Single(Wrapper),
+    Stages(Vec<Wrapper>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Json {
+    select: Option<Selection>,
+    #[serde(skip)]
+    jmespath: Compiled<jmespath::Expression<'static>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
-pub struct Json {}
+pub struct JsonSeq {
+    select: Option<Selection>,
+    #[serde(skip)]
+    jmespath: Compiled<jmespath::Expression<'static>>,
+}
 
+/// Projects a document down to the relevant part at wrap time.
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
-pub struct JsonSeq {}
+#[serde(rename_all = "lowercase")]
+pub enum Selection {
+    /// RFC 6901 JSON Pointer, e.g. `/0/NetworkSettings/Ports`, missing paths select `null`.
+    Pointer(String),
+    /// JMESPath expression, e.g. `[].{name: Name, state: State.Status}`.
+    Jmespath(String),
+}
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct RawLines {}
@@ -30,130 +73,663 @@ pub struct Regexp {
     flags: Option<String>,
     #[serde(default)]
     map_key_val: bool,
+    /// Per group (per key with `map_key_val`) conversions of the captured strings.
+    #[serde(default)]
+    types: HashMap<String, Coercion>,
+    /// The built expression and whether a single match yields a map instead of a list.
+    #[serde(skip)]
+    regex: Compiled<(Regex, bool)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct CmdLine {
+    #[serde(default)]
+    types: HashMap<String, Coercion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Yaml {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Toml {}
+
+/// Elements become maps with `@name` keys for attributes and `#text` for the text content,
+/// repeated child elements are collected into a list and text-only elements collapse to a string.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Xml {
+    xpath: Option<String>,
+    #[serde(default)]
+    namespaces: HashMap<String, String>,
+    #[serde(skip)]
+    compiled: Compiled<sxd_xpath::XPath>,
+}
+
+/// Splits lines into fields by a delimiter character, or by whitespace runs when none is given.
+/// The last named column takes the remaining fields, if there are more fields than columns,
+/// the columns missing from shorter lines are `null`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Columns {
+    delimiter: Option<char>,
+    #[serde(default)]
+    columns: Vec<String>,
+    #[serde(default)]
+    header: bool,
+    comment: Option<String>,
+    quote: Option<char>,
+    #[serde(default)]
+    types: HashMap<String, Coercion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Coercion {
+    String,
+    Int,
+    Float,
+    Bool,
+    /// Split on the given delimiter, e.g. `{"list": ","}`.
+    List(String),
+    /// Permission bits like `0644`, converted to a number.
+    Octal,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EnvFile {}
+
+/// `Keyword value...` configuration (sshd_config, chrony.conf, auditd.conf, ...). Keywords are
+/// case-insensitive, the first occurrence wins unless the keyword is listed in `multi`, then
+/// all values are collected. Conditional sections (`Match`) go to `conditional` in order.
+/// Included files are read through the runner, a conditional section they open ends with them.
+/// Relative include patterns are resolved against `include_base`, by default the directory of
+/// the parsed file, like sshd does with `/etc/ssh`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Keyword {
+    #[serde(default)]
+    multi: Vec<String>,
+    #[serde(default)]
+    last_match: bool,
+    #[serde(default = "default_conditional_keywords")]
+    conditional_keywords: Vec<String>,
+    #[serde(default = "default_include_keywords")]
+    include_keywords: Vec<String>,
+    include_base: Option<String>,
+}
+
+fn default_conditional_keywords() -> Vec<String> {
+    vec!["match".to_string()]
 }
 
+fn default_include_keywords() -> Vec<String> {
+    vec!["include".to_string()]
+}
+
+// Guards against include loops.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// INI-style configuration (systemd units, yum repos, ...) as sections of keys. The last
+/// assignment of a key wins unless the key is listed in `multi`, then its values are always
+/// collected in a list which an empty assignment (e.g. `ExecStart=`) resets.
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
-pub struct CmdLine {}
+pub struct Ini {
+    #[serde(default)]
+    case_insensitive: bool,
+    #[serde(default)]
+    multi: Vec<String>,
+    /// Merges the sources (e.g. a unit file and its drop-ins) into a single entry, with
+    /// `sources` naming the source which last set each key. Without merging each entry comes
+    /// from a single source and has no `sources`.
+    #[serde(default)]
+    merge: bool,
+}
 
 pub trait Wrapping {
-    fn wrap(&self, input: &str) -> Value;
-    fn wrap_all(&self, inputs: &Vec<(String, String)>) -> Value {
-        let mut container = Vec::new();
-        for input in inputs {
-            let mut entry: Map<String, Value> = Map::new();
-            entry.insert("source".to_string(), input.0.clone().into());
-            entry.insert("data".to_string(), self.wrap(&input.1));
-            container.push(entry);
+    /// Builds whatever the wrapper needs ahead of the first input, reporting syntax errors.
+    fn compile(&self) -> Result<()> {
+        Ok(())
+    }
+    fn wrap(&self, input: &str) -> Result<Value>;
+    fn wrap_all(&self, inputs: &[(String, String)]) -> Result<Value> {
+        wrap_each(self, inputs)
+    }
+    /// For wrappers which need to read further sources (e.g. included files) through the runner.
+    fn wrap_all_with_runner(
+        &self,
+        _runner: &mut Runner,
+        inputs: &[(String, String)],
+    ) -> Result<Value> {
+        self.wrap_all(inputs)
+    }
+    /// Wraps the output of a previous pipeline stage. Strings are wrapped as usual and lists
+    /// element by element.
+    fn wrap_value(&self, input: &Value) -> Result<Value> {
+        match input {
+            Value::String(s) => self.wrap(s),
+            Value::Array(items) => items.iter().map(|i| self.wrap_value(i)).collect(),
+            _ => Err(Error::Wrapper(format!(
+                "Unable to wrap {}, text or a list of texts is expected! Only json takes \
+                 structured values from a previous stage.",
+                kind(input)
+            ))),
+        }
+    }
+}
+
+/// Kind of a value for error messages, which shouldn't dump whole documents.
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "an object",
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline::Single(Wrapper::default())
+    }
+}
+
+// Parses stage by stage, an untagged enum would report any typo as not matching a variant.
+impl<'de> Deserialize<'de> for Pipeline {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Array(stages) if stages.is_empty() => Err(de::Error::custom(
+                "A wrapper pipeline needs at least one stage!",
+            )),
+            Value::Array(stages) => stages
+                .into_iter()
+                .enumerate()
+                .map(|(i, stage)| {
+                    Wrapper::deserialize(stage)
+                        .map_err(|e| de::Error::custom(format!("Wrapper stage {}: {}", i + 1, e)))
+                })
+                .collect::<std::result::Result<_, _>>()
+                .map(Pipeline::Stages),
+            wrapper => Wrapper::deserialize(wrapper)
+                .map(Pipeline::Single)
+                .map_err(de::Error::custom),
+        }
+    }
+}
+
+impl Pipeline {
+    fn stages(&self) -> &[Wrapper] {
+        match self {
+            Pipeline::Single(wrapper) => std::slice::from_ref(wrapper),
+            Pipeline::Stages(stages) => stages.as_slice(),
+        }
+    }
+}
+
+/// Names the stage of a multi-stage pipeline a wrapper error comes from.
+fn stage_error(stages: &[Wrapper], i: usize, error: Error) -> Error {
+    match error {
+        Error::Wrapper(message) if stages.len() > 1 => Error::Wrapper(format!(
+            "Stage {} ({}): {}",
+            i + 1,
+            stages[i].name(),
+            message
+        )),
+        error => error,
+    }
+}
+
+impl Wrapping for Pipeline {
+    fn compile(&self) -> Result<()> {
+        if self.stages().is_empty() {
+            return Err(Error::Wrapper(
+                "A wrapper pipeline needs at least one stage!".to_string(),
+            ));
+        }
+        self.stages().iter().try_for_each(|stage| stage.compile())
+    }
+    fn wrap(&self, input: &str) -> Result<Value> {
+        self.wrap_value(&input.into())
+    }
+    fn wrap_all_with_runner(
+        &self,
+        runner: &mut Runner,
+        inputs: &[(String, String)],
+    ) -> Result<Value> {
+        let stages = self.stages();
+        let Some((first, rest)) = stages.split_first() else {
+            return Err(Error::Wrapper(
+                "A wrapper pipeline needs at least one stage!".to_string(),
+            ));
+        };
+        let mut container = first
+            .wrap_all_with_runner(runner, inputs)
+            .map_err(|e| stage_error(stages, 0, e))?;
+        for entry in container.as_array_mut().into_iter().flatten() {
+            if let Some(data) = entry.get_mut("data") {
+                for (i, stage) in rest.iter().enumerate() {
+                    *data = stage
+                        .wrap_value(data)
+                        .map_err(|e| stage_error(stages, i + 1, e))?;
+                }
+            }
         }
-        container.into()
+        Ok(container)
+    }
+    fn wrap_value(&self, input: &Value) -> Result<Value> {
+        let stages = self.stages();
+        stages
+            .iter()
+            .enumerate()
+            .try_fold(input.clone(), |value, (i, stage)| {
+                stage
+                    .wrap_value(&value)
+                    .map_err(|e| stage_error(stages, i, e))
+            })
+    }
+}
+
+fn wrap_each<W: Wrapping + ?Sized>(wrapper: &W, inputs: &[(String, String)]) -> Result<Value> {
+    let mut container = Vec::new();
+    for input in inputs {
+        let mut entry: Map<String, Value> = Map::new();
+        entry.insert("source".to_string(), input.0.clone().into());
+        entry.insert("data".to_string(), wrapper.wrap(&input.1)?);
+        container.push(entry);
     }
+    Ok(container.into())
 }
 
 impl Default for Wrapper {
     fn default() -> Self {
-        Wrapper::Json(Json {})
+        Wrapper::Json(Json::default())
+    }
+}
+
+impl Wrapper {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Json(_) => "json",
+            Self::JsonSeq(_) => "json-seq",
+            Self::RawLines(_) => "raw-lines",
+            Self::Regexp(_) => "regexp",
+            Self::CmdLine(_) => "cmd-line",
+            Self::Ini(_) => "ini",
+            Self::Yaml(_) => "yaml",
+            Self::Toml(_) => "toml",
+            Self::Xml(_) => "xml",
+            Self::Columns(_) => "columns",
+            Self::EnvFile(_) => "env-file",
+            Self::Keyword(_) => "keyword",
+        }
     }
 }
 
 // TODO: enum_dispatch
 impl Wrapping for Wrapper {
-    fn wrap(&self, input: &str) -> Value {
+    fn compile(&self) -> Result<()> {
+        match self {
+            Self::Json(wrp) => wrp.compile(),
+            Self::JsonSeq(wrp) => wrp.compile(),
+            Self::Regexp(wrp) => wrp.compile(),
+            Self::Xml(wrp) => wrp.compile(),
+            _ => Ok(()),
+        }
+    }
+    fn wrap(&self, input: &str) -> Result<Value> {
         match self {
             Self::Json(wrp) => wrp.wrap(input),
             Self::JsonSeq(wrp) => wrp.wrap(input),
             Self::RawLines(wrp) => wrp.wrap(input),
             Self::Regexp(wrp) => wrp.wrap(input),
             Self::CmdLine(wrp) => wrp.wrap(input),
+            Self::Ini(wrp) => wrp.wrap(input),
+            Self::Yaml(wrp) => wrp.wrap(input),
+            Self::Toml(wrp) => wrp.wrap(input),
+            Self::Xml(wrp) => wrp.wrap(input),
+            Self::Columns(wrp) => wrp.wrap(input),
+            Self::EnvFile(wrp) => wrp.wrap(input),
+            Self::Keyword(wrp) => wrp.wrap(input),
         }
     }
-    fn wrap_all(&self, input: &Vec<(String, String)>) -> Value {
+    fn wrap_all(&self, input: &[(String, String)]) -> Result<Value> {
         match self {
             Self::Json(wrp) => wrp.wrap_all(input),
             Self::JsonSeq(wrp) => wrp.wrap_all(input),
             Self::RawLines(wrp) => wrp.wrap_all(input),
             Self::Regexp(wrp) => wrp.wrap_all(input),
             Self::CmdLine(wrp) => wrp.wrap_all(input),
+            Self::Ini(wrp) => wrp.wrap_all(input),
+            Self::Yaml(wrp) => wrp.wrap_all(input),
+            Self::Toml(wrp) => wrp.wrap_all(input),
+            Self::Xml(wrp) => wrp.wrap_all(input),
+            Self::Columns(wrp) => wrp.wrap_all(input),
+            Self::EnvFile(wrp) => wrp.wrap_all(input),
+            Self::Keyword(wrp) => wrp.wrap_all(input),
+        }
+    }
+    fn wrap_all_with_runner(
+        &self,
+        runner: &mut Runner,
+        input: &[(String, String)],
+    ) -> Result<Value> {
+        match self {
+            Self::Keyword(wrp) => wrp.wrap_all_with_runner(runner, input),
+            _ => self.wrap_all(input),
+        }
+    }
+    fn wrap_value(&self, input: &Value) -> Result<Value> {
+        match self {
+            Self::Json(wrp) => wrp.wrap_value(input),
+            Self::JsonSeq(wrp) => wrp.wrap_value(input),
+            Self::RawLines(wrp) => wrp.wrap_value(input),
+            Self::Regexp(wrp) => wrp.wrap_value(input),
+            Self::CmdLine(wrp) => wrp.wrap_value(input),
+            Self::Ini(wrp) => wrp.wrap_value(input),
+            Self::Yaml(wrp) => wrp.wrap_value(input),
+            Self::Toml(wrp) => wrp.wrap_value(input),
+            Self::Xml(wrp) => wrp.wrap_value(input),
+            Self::Columns(wrp) => wrp.wrap_value(input),
+            Self::EnvFile(wrp) => wrp.wrap_value(input),
+            Self::Keyword(wrp) => wrp.wrap_value(input),
         }
     }
 }
 
 impl Wrapping for Json {
-    fn wrap(&self, input: &str) -> Value {
-        serde_json::from_str(&input).expect("Unable to wrap JSON output!")
+    fn compile(&self) -> Result<()> {
+        compile_selection(&self.select, &self.jmespath)
+    }
+    fn wrap(&self, input: &str) -> Result<Value> {
+        let value = serde_json::from_str(input)
+            .map_err(|e| Error::Wrapper(format!("Unable to wrap JSON output: {}", e)))?;
+        select(&self.select, &self.jmespath, value)
+    }
+    fn wrap_value(&self, input: &Value) -> Result<Value> {
+        match input {
+            Value::String(s) => self.wrap(s),
+            // Raw lines, e.g. from raw-lines, are documents of their own.
+            Value::Array(items) if items.iter().all(Value::is_string) => {
+                items.iter().map(|i| self.wrap_value(i)).collect()
+            }
+            // Already structured, e.g. by a previous stage, the selection applies to the whole.
+            _ => select(&self.select, &self.jmespath, input.clone()),
+        }
+    }
+}
+
+fn jmespath_expression<'a>(
+    expr: &str,
+    jmespath: &'a Compiled<jmespath::Expression<'static>>,
+) -> Result<&'a jmespath::Expression<'static>> {
+    jmespath.get_or_try_init(|| jmespath::compile(expr).map_err(|e| Error::Wrapper(e.to_string())))
+}
+
+fn compile_selection(
+    selection: &Option<Selection>,
+    jmespath: &Compiled<jmespath::Expression<'static>>,
+) -> Result<()> {
+    match selection {
+        Some(Selection::Jmespath(expr)) => jmespath_expression(expr, jmespath).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+fn select(
+    selection: &Option<Selection>,
+    jmespath: &Compiled<jmespath::Expression<'static>>,
+    value: Value,
+) -> Result<Value> {
+    match selection {
+        None => Ok(value),
+        Some(Selection::Pointer(pointer)) => {
+            Ok(value.pointer(pointer).cloned().unwrap_or(Value::Null))
+        }
+        Some(Selection::Jmespath(expr)) => {
+            let result = jmespath_expression(expr, jmespath)?
+                .search(value)
+                .map_err(|e| Error::Wrapper(e.to_string()))?;
+            serde_json::to_value(&*result).map_err(|e| Error::Wrapper(e.to_string()))
+        }
     }
 }
 
 impl Wrapping for JsonSeq {
-    fn wrap(&self, input: &str) -> Value {
+    fn compile(&self) -> Result<()> {
+        compile_selection(&self.select, &self.jmespath)
+    }
+    fn wrap(&self, input: &str) -> Result<Value> {
         let mut container = Vec::new();
         let reader = jsonseq::JsonSeqReader::new(Cursor::new(input));
         for item in reader {
-            if let Ok(value) = item {
-                container.push(value);
+            let value = item.map_err(|e| {
+                let e = match e {
+                    jsonseq::Error::JsonError(e) => e.to_string(),
+                    e => e.to_string(),
+                };
+                Error::Wrapper(format!("Unable to wrap JSON-seq output: {}", e))
+            })?;
+            container.push(select(&self.select, &self.jmespath, value)?);
+        }
+        Ok(container.into())
+    }
+}
+
+impl Wrapping for Yaml {
+    fn wrap(&self, input: &str) -> Result<Value> {
+        let mut container = Vec::new();
+        for document in serde_yaml::Deserializer::from_str(input) {
+            let value = Value::deserialize(document)
+                .map_err(|e| Error::Wrapper(format!("Unable to wrap YAML output: {}", e)))?;
+            container.push(value);
+        }
+        Ok(container.into())
+    }
+}
+
+impl Wrapping for Toml {
+    fn wrap(&self, input: &str) -> Result<Value> {
+        let value: toml::Value = toml::from_str(input)
+            .map_err(|e| Error::Wrapper(format!("Unable to wrap TOML output: {}", e)))?;
+        Ok(toml_to_json(value))
+    }
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => s.into(),
+        toml::Value::Integer(i) => i.into(),
+        toml::Value::Float(f) => f.into(),
+        toml::Value::Boolean(b) => b.into(),
+        // Serde would turn it into a private wrapper struct, the RFC 3339 string is more useful.
+        toml::Value::Datetime(d) => d.to_string().into(),
+        toml::Value::Array(a) => a.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(t) => t
+            .into_iter()
+            .map(|(k, v)| (k, toml_to_json(v)))
+            .collect::<Map<String, Value>>()
+            .into(),
+    }
+}
+
+impl Xml {
+    fn xpath(&self, xpath: &str) -> Result<&sxd_xpath::XPath> {
+        self.compiled.get_or_try_init(|| {
+            sxd_xpath::Factory::new()
+                .build(xpath)
+                .map_err(|e| Error::Wrapper(format!("Can't build the XPath expression: {}", e)))?
+                .ok_or_else(|| Error::Wrapper("The XPath expression is empty!".to_string()))
+        })
+    }
+}
+
+impl Wrapping for Xml {
+    fn compile(&self) -> Result<()> {
+        match &self.xpath {
+            Some(xpath) => self.xpath(xpath).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+    fn wrap(&self, input: &str) -> Result<Value> {
+        let package = sxd_document::parser::parse(input)
+            .map_err(|e| Error::Wrapper(format!("Unable to wrap XML output: {}", e)))?;
+        let document = package.as_document();
+        let Some(xpath) = &self.xpath else {
+            let mut root = Map::new();
+            for child in document.root().children() {
+                if let Some(element) = child.element() {
+                    root.insert(
+                        element.name().local_part().to_string(),
+                        xml_to_json(element),
+                    );
+                }
+            }
+            return Ok(root.into());
+        };
+
+        let xpath = self.xpath(xpath)?;
+        let mut context = sxd_xpath::Context::new();
+        for (prefix, uri) in &self.namespaces {
+            context.set_namespace(prefix, uri);
+        }
+        let value = xpath
+            .evaluate(&context, document.root())
+            .map_err(|e| Error::Wrapper(format!("Can't evaluate the XPath expression: {}", e)))?;
+        Ok(match value {
+            sxd_xpath::Value::Boolean(b) => b.into(),
+            sxd_xpath::Value::Number(n) => n.into(),
+            sxd_xpath::Value::String(s) => s.into(),
+            sxd_xpath::Value::Nodeset(nodes) => nodes
+                .document_order()
+                .into_iter()
+                .map(|node| match node {
+                    Node::Element(element) => xml_to_json(element),
+                    Node::Attribute(attribute) => attribute.value().into(),
+                    node => node.string_value().into(),
+                })
+                .collect(),
+        })
+    }
+}
+
+fn xml_to_json(element: Element) -> Value {
+    let mut map = Map::new();
+    for attribute in element.attributes() {
+        map.insert(
+            format!("@{}", attribute.name().local_part()),
+            attribute.value().into(),
+        );
+    }
+    let mut text = String::new();
+    for child in element.children() {
+        match child {
+            ChildOfElement::Element(child) => {
+                let name = child.name().local_part().to_string();
+                let value = xml_to_json(child);
+                match map.get_mut(&name) {
+                    Some(Value::Array(values)) => values.push(value),
+                    Some(prev) => *prev = vec![prev.take(), value].into(),
+                    None => {
+                        map.insert(name, value);
+                    }
+                }
             }
+            ChildOfElement::Text(t) => text.push_str(t.text()),
+            _ => {}
         }
-        container.into()
     }
+    let text = text.trim();
+    if map.is_empty() {
+        return text.into();
+    }
+    if !text.is_empty() {
+        map.insert("#text".to_string(), text.into());
+    }
+    map.into()
 }
 
 impl Wrapping for RawLines {
-    fn wrap(&self, input: &str) -> Value {
+    fn wrap(&self, input: &str) -> Result<Value> {
         let mut container = Vec::new();
         for line in input.lines() {
             container.push(Value::String(line.to_string()));
         }
-        container.into()
+        Ok(container.into())
     }
 }
 
-impl Wrapping for Regexp {
-    fn wrap(&self, input: &str) -> Value {
-        let mut single_map = false;
-        let mut rb = &mut RegexBuilder::new(&self.expr);
-        rb = rb.multi_line(true);
-        if let Some(flags) = &self.flags {
-            for f in flags.chars() {
-                rb = match f {
-                    'M' => {
-                        single_map = true;
-                        rb.multi_line(false)
-                    }
-                    's' => rb.dot_matches_new_line(true),
-                    'i' => rb.case_insensitive(true),
-                    _ => rb,
-                };
+impl Regexp {
+    fn regex(&self) -> Result<&(Regex, bool)> {
+        self.regex.get_or_try_init(|| {
+            let mut single_map = false;
+            let mut rb = &mut RegexBuilder::new(&self.expr);
+            rb = rb.multi_line(true);
+            if let Some(flags) = &self.flags {
+                for f in flags.chars() {
+                    rb = match f {
+                        'M' => {
+                            single_map = true;
+                            rb.multi_line(false)
+                        }
+                        's' => rb.dot_matches_new_line(true),
+                        'i' => rb.case_insensitive(true),
+                        _ => rb,
+                    };
+                }
             }
-        }
-        let re = rb.build().expect("Can't build the expression!");
-        let group_names: Vec<&str> = re.capture_names().skip(1).map(|x| x.unwrap()).collect();
+            let re = rb
+                .build()
+                .map_err(|e| Error::Wrapper(format!("Can't build the expression: {}", e)))?;
+            let names: Vec<Option<&str>> = re.capture_names().skip(1).collect();
+            if names.contains(&None) && names.iter().any(Option::is_some) {
+                return Err(Error::Wrapper(
+                    "Unnamed groups can't be mixed with named groups, use '(?:...)' for grouping!"
+                        .to_string(),
+                ));
+            }
+            if self.map_key_val {
+                let key_val: HashSet<_> =
+                    HashSet::from_iter(vec![Some("key"), Some("val")]);
+                if !re.capture_names().skip(1).all(|name| key_val.contains(&name)) {
+                    return Err(Error::Wrapper("The 'map_key_val' option of the Regex Wrapper requires 'key' and 'val' named groups defined in the expression!".to_string()));
+                }
+            }
+            Ok((re, single_map))
+        })
+    }
+}
+
+impl Wrapping for Regexp {
+    fn compile(&self) -> Result<()> {
+        self.regex().map(|_| ())
+    }
+    fn wrap(&self, input: &str) -> Result<Value> {
+        let (re, single_map) = self.regex()?;
+        // Named and unnamed groups are not mixed, checked by `regex()`.
+        let group_names: Vec<&str> = re.capture_names().skip(1).flatten().collect();
         if self.map_key_val {
-            let key_val: HashSet<_> =
-                HashSet::from_iter(vec!["key".to_string(), "val".to_string()]);
-            if !group_names.iter().all(|&item| key_val.contains(item)) {
-                panic!("The 'map_key_val' option of the Regex Wrapper requires 'key' and 'val' named groups defined in the expression!");
-            } else {
-                let mut caps_map: Map<String, Value> = Map::new();
-                for caps in re.captures_iter(input) {
-                    let mut key = Value::Null;
-                    let mut val = Value::Null;
-                    for name in &group_names {
-                        if let Some(m) = caps.name(name) {
-                            match *name {
-                                "key" => key = m.as_str().into(),
-                                "val" => val = m.as_str().into(),
-                                _ => {}
-                            }
+            let mut caps_map: Map<String, Value> = Map::new();
+            for caps in re.captures_iter(input) {
+                let mut key = Value::Null;
+                let mut val = Value::Null;
+                for name in &group_names {
+                    if let Some(m) = caps.name(name) {
+                        match *name {
+                            "key" => key = m.as_str().into(),
+                            "val" => val = m.as_str().into(),
+                            _ => {}
                         }
                     }
-                    if key != Value::Null {
-                        caps_map.insert(key.as_str().unwrap().to_string(), val);
-                    } else {
-                        // TODO: Should be error?
-                        warn!("The val='{}' does not have a corresponding key!", val);
+                }
+                if let Value::String(key) = key {
+                    if let Value::String(v) = &val {
+                        val = coerce_field(&self.types, &key, v)?;
                     }
+                    caps_map.insert(key, val);
+                } else {
+                    // TODO: Should be error?
+                    warn!("The val='{}' does not have a corresponding key!", val);
                 }
-                caps_map.into()
             }
+            Ok(caps_map.into())
         } else {
             let mut container: Vec<Value> = Vec::new();
             for caps in re.captures_iter(input) {
@@ -161,48 +737,514 @@ impl Wrapping for Regexp {
                     let mut caps_map: Map<String, Value> = Map::new();
                     for name in &group_names {
                         if let Some(m) = caps.name(name) {
-                            caps_map.insert(name.to_string(), m.as_str().to_string().into());
+                            caps_map.insert(
+                                name.to_string(),
+                                coerce_field(&self.types, name, m.as_str())?,
+                            );
                         }
                     }
                     container.push(caps_map.into());
                 } else {
+                    // A group which did not participate in the match, e.g. `(...)?`, is null.
                     container.push(
                         caps.iter()
                             .skip(1)
-                            .map(|x| x.unwrap().as_str().to_string())
+                            .map(|x| x.map_or(Value::Null, |m| m.as_str().into()))
                             .collect(),
                     );
                 }
             }
-            if container.len() == 1 && single_map {
-                container.pop().unwrap()
+            if container.len() == 1 && *single_map {
+                Ok(container.pop().unwrap())
             } else {
-                container.into()
+                Ok(container.into())
             }
         }
     }
 }
 
 impl Wrapping for CmdLine {
-    fn wrap(&self, input: &str) -> Value {
+    fn wrap(&self, input: &str) -> Result<Value> {
         let line = input
             .lines()
             .next()
-            .expect("At least one line is expected!");
+            .ok_or_else(|| Error::Wrapper("At least one line is expected!".to_string()))?;
         let args = line.split_whitespace();
         let mut map = Map::new();
         for arg in args {
             let pair: Vec<&str> = arg.splitn(2, '=').collect();
             if pair.len() > 1 {
-                map.insert(pair[0].to_string(), pair[1].into());
+                map.insert(
+                    pair[0].to_string(),
+                    coerce_field(&self.types, pair[0], pair[1])?,
+                );
             } else {
                 map.insert(pair[0].to_string(), Value::from(true));
             }
         }
+        Ok(map.into())
+    }
+}
+
+impl Wrapping for Columns {
+    fn wrap(&self, input: &str) -> Result<Value> {
+        let mut columns = self.columns.clone();
+        let mut container: Vec<Value> = Vec::new();
+        let mut lines = input.lines().filter(|l| {
+            let l = l.trim();
+            !l.is_empty()
+                && self
+                    .comment
+                    .as_ref()
+                    .is_none_or(|c| !l.starts_with(c.as_str()))
+        });
+        if self.header {
+            if let Some(line) = lines.next() {
+                columns = self.split(line);
+            }
+        }
+        for line in lines {
+            let mut fields = self.split(line);
+            if columns.is_empty() {
+                container.push(fields.into());
+                continue;
+            }
+            if fields.len() > columns.len() {
+                let tail = fields.split_off(columns.len() - 1);
+                let delimiter = self.delimiter.unwrap_or(' ').to_string();
+                fields.push(tail.join(&delimiter));
+            }
+            let mut row = Map::new();
+            let mut fields = fields.into_iter();
+            for name in &columns {
+                let value = match fields.next() {
+                    Some(field) => coerce_field(&self.types, name, &field)?,
+                    None => Value::Null,
+                };
+                row.insert(name.clone(), value);
+            }
+            container.push(row.into());
+        }
+        Ok(container.into())
+    }
+}
+
+impl Columns {
+    fn split(&self, line: &str) -> Vec<String> {
+        let whitespace = self.delimiter.is_none();
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut started = false;
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if Some(c) == self.quote {
+                // A doubled quote inside a quoted field stands for the quote itself.
+                if quoted && chars.peek() == Some(&c) {
+                    field.push(c);
+                    chars.next();
+                } else {
+                    quoted = !quoted;
+                }
+                started = true;
+            } else if !quoted && (Some(c) == self.delimiter || whitespace && c.is_whitespace()) {
+                if started || !whitespace {
+                    fields.push(std::mem::take(&mut field));
+                }
+                started = false;
+            } else {
+                field.push(c);
+                started = true;
+            }
+        }
+        if started || !whitespace {
+            fields.push(field);
+        }
+        fields
+    }
+}
+
+impl Coercion {
+    pub fn coerce(&self, input: &str) -> Result<Value> {
+        let input = input.trim();
+        let error = || Error::Wrapper(format!("Can't convert '{}' to {:?}!", input, self));
+        match self {
+            Coercion::String => Ok(input.into()),
+            Coercion::Int => input.parse::<i64>().map(Value::from).map_err(|_| error()),
+            Coercion::Float => input.parse::<f64>().map(Value::from).map_err(|_| error()),
+            Coercion::Bool => match input.to_lowercase().as_str() {
+                "y" | "yes" | "1" | "true" | "on" => Ok(true.into()),
+                "n" | "no" | "0" | "false" | "off" => Ok(false.into()),
+                _ => Err(error()),
+            },
+            Coercion::List(delimiter) => Ok(input
+                .split(delimiter.as_str())
+                .map(|i| i.trim())
+                .filter(|i| !i.is_empty())
+                .collect()),
+            Coercion::Octal => i64::from_str_radix(input, 8)
+                .map(Value::from)
+                .map_err(|_| error()),
+        }
+    }
+}
+
+fn coerce_field(types: &HashMap<String, Coercion>, name: &str, input: &str) -> Result<Value> {
+    match types.get(name) {
+        Some(coercion) => coercion.coerce(input),
+        None => Ok(input.into()),
+    }
+}
+
+impl Wrapping for EnvFile {
+    fn wrap(&self, input: &str) -> Result<Value> {
+        Ok(parse_env(input).into())
+    }
+}
+
+/// Parses shell style `KEY=VALUE` assignments with quoting, escapes, line continuations,
+/// comments and optional `export`. Variable references are not expanded.
+pub fn parse_env(input: &str) -> Map<String, Value> {
+    let mut map = Map::new();
+    let mut chars = input.chars().peekable();
+    let skip_line = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        for c in chars.by_ref() {
+            if c == '\n' {
+                break;
+            }
+        }
+    };
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+        if first == '#' {
+            skip_line(&mut chars);
+            continue;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.') {
+            key.push(c);
+        }
+        if key == "export" && chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+            while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+            key.clear();
+            while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.') {
+                key.push(c);
+            }
+        }
+        if key.is_empty() || chars.next_if_eq(&'=').is_none() {
+            skip_line(&mut chars);
+            continue;
+        }
+        let mut value = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            match c {
+                '\'' => {
+                    for c in chars.by_ref() {
+                        if c == '\'' {
+                            break;
+                        }
+                        value.push(c);
+                    }
+                }
+                '"' => {
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => match chars.next() {
+                                Some('\n') | None => {}
+                                Some(c @ ('"' | '\\' | '$' | '`')) => value.push(c),
+                                Some(c) => {
+                                    value.push('\\');
+                                    value.push(c);
+                                }
+                            },
+                            c => value.push(c),
+                        }
+                    }
+                }
+                '\\' => match chars.next() {
+                    Some('\n') | None => {}
+                    Some(c) => value.push(c),
+                },
+                c => value.push(c),
+            }
+        }
+        // Anything after the value, e.g. a trailing comment, is ignored.
+        if chars.peek().is_some_and(|c| *c != '\n') {
+            skip_line(&mut chars);
+        }
+        map.insert(key, value.into());
+    }
+    map
+}
+
+impl Wrapping for Keyword {
+    fn wrap(&self, input: &str) -> Result<Value> {
+        let lines = input
+            .lines()
+            .filter(|line| match Self::split(line) {
+                Some((keyword, _)) if Self::listed(&self.include_keywords, &keyword) => {
+                    warn!(
+                        "Ignoring '{}', includes need the probe's runner!",
+                        line.trim()
+                    );
+                    false
+                }
+                _ => true,
+            })
+            .map(|l| KeywordLine::Line(l.to_string()))
+            .collect();
+        Ok(self.parse(lines))
+    }
+
+    fn wrap_all_with_runner(
+        &self,
+        runner: &mut Runner,
+        inputs: &[(String, String)],
+    ) -> Result<Value> {
+        let mut container = Vec::new();
+        for input in inputs {
+            let base = match &self.include_base {
+                Some(base) => base.clone(),
+                None => std::path::Path::new(&input.0)
+                    .parent()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            };
+            let lines = self.expand(runner, &input.1, &base, 0)?;
+            let mut entry: Map<String, Value> = Map::new();
+            entry.insert("source".to_string(), input.0.clone().into());
+            entry.insert("data".to_string(), self.parse(lines));
+            container.push(entry);
+        }
+        Ok(container.into())
+    }
+}
+
+/// Line of a `Keyword` input with the included files expanded in place, delimited so that
+/// a conditional section opened by an included file ends with it.
+enum KeywordLine {
+    Line(String),
+    IncludeStart,
+    IncludeEnd,
+}
+
+impl Keyword {
+    fn listed(keywords: &[String], keyword: &str) -> bool {
+        keywords.iter().any(|k| k.eq_ignore_ascii_case(keyword))
+    }
+
+    fn split(line: &str) -> Option<(String, String)> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let end = line
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(line.len());
+        let (keyword, args) = line.split_at(end);
+        let args = args.trim_start();
+        let args = args.strip_prefix('=').unwrap_or(args).trim();
+        Some((keyword.to_lowercase(), args.to_string()))
+    }
+
+    /// Replaces include lines with the lines of the included files, in place.
+    fn expand(
+        &self,
+        runner: &mut Runner,
+        input: &str,
+        base: &str,
+        depth: usize,
+    ) -> Result<Vec<KeywordLine>> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(Error::Wrapper("Too many nested includes!".to_string()));
+        }
+        let mut lines = Vec::new();
+        for line in input.lines() {
+            match Self::split(line) {
+                Some((keyword, args)) if Self::listed(&self.include_keywords, &keyword) => {
+                    for pattern in args.split_whitespace() {
+                        let pattern = if pattern.starts_with('/') || base.is_empty() {
+                            pattern.to_string()
+                        } else {
+                            format!("{}/{}", base, pattern)
+                        };
+                        for path in runner.unglob_path(&pattern) {
+                            let content = runner.cat(&path);
+                            lines.push(KeywordLine::IncludeStart);
+                            lines.extend(self.expand(runner, &content, base, depth + 1)?);
+                            lines.push(KeywordLine::IncludeEnd);
+                        }
+                    }
+                }
+                _ => lines.push(KeywordLine::Line(line.to_string())),
+            }
+        }
+        Ok(lines)
+    }
+
+    fn set(&self, settings: &mut Map<String, Value>, keyword: String, args: String) {
+        if Self::listed(&self.multi, &keyword) {
+            settings
+                .entry(keyword)
+                .or_insert_with(|| Vec::<Value>::new().into())
+                .as_array_mut()
+                .unwrap()
+                .push(args.into());
+        } else if self.last_match || !settings.contains_key(&keyword) {
+            settings.insert(keyword, args.into());
+        }
+    }
+
+    fn parse(&self, lines: Vec<KeywordLine>) -> Value {
+        let mut global = Map::new();
+        let mut conditional: Vec<(String, String, Map<String, Value>)> = Vec::new();
+        // The conditional section settings go to, if any, and the ones of the including files.
+        let mut section: Option<usize> = None;
+        let mut including = Vec::new();
+        for line in lines {
+            let line = match line {
+                KeywordLine::Line(line) => line,
+                KeywordLine::IncludeStart => {
+                    including.push(section);
+                    continue;
+                }
+                KeywordLine::IncludeEnd => {
+                    section = including.pop().flatten();
+                    continue;
+                }
+            };
+            let Some((keyword, args)) = Self::split(&line) else {
+                continue;
+            };
+            if Self::listed(&self.conditional_keywords, &keyword) {
+                conditional.push((keyword, args, Map::new()));
+                section = Some(conditional.len() - 1);
+            } else if let Some(i) = section {
+                self.set(&mut conditional[i].2, keyword, args);
+            } else {
+                self.set(&mut global, keyword, args);
+            }
+        }
+        let conditional: Vec<Value> = conditional
+            .into_iter()
+            .map(|(keyword, criteria, settings)| {
+                let mut block = Map::new();
+                block.insert("keyword".to_string(), keyword.into());
+                block.insert("criteria".to_string(), criteria.into());
+                block.insert("settings".to_string(), settings.into());
+                block.into()
+            })
+            .collect();
+        let mut map = Map::new();
+        map.insert("global".to_string(), global.into());
+        map.insert("conditional".to_string(), conditional.into());
         map.into()
     }
 }
 
+impl Wrapping for Ini {
+    fn wrap(&self, input: &str) -> Result<Value> {
+        let mut sections = Map::new();
+        self.parse(input, "", &mut sections, &mut Map::new());
+        Ok(sections.into())
+    }
+
+    fn wrap_all(&self, inputs: &[(String, String)]) -> Result<Value> {
+        if !self.merge || inputs.is_empty() {
+            return wrap_each(self, inputs);
+        }
+        // Drop-ins are applied in the probe order as if they were appended to the first source.
+        let mut merged = Map::new();
+        let mut sources = Map::new();
+        for (source, input) in inputs {
+            self.parse(input, source, &mut merged, &mut sources);
+        }
+        let mut entry: Map<String, Value> = Map::new();
+        entry.insert("source".to_string(), inputs[0].0.clone().into());
+        entry.insert("data".to_string(), merged.into());
+        entry.insert("sources".to_string(), sources.into());
+        Ok(vec![entry].into())
+    }
+}
+
+impl Ini {
+    /// Adds the sections and keys of the input, recording the source which last set each key.
+    fn parse(
+        &self,
+        input: &str,
+        source: &str,
+        sections: &mut Map<String, Value>,
+        sources: &mut Map<String, Value>,
+    ) {
+        // Keys before the first section header go to the unnamed section.
+        let mut section = String::new();
+        let mut logical = String::new();
+        for line in input.lines() {
+            let line = line.trim();
+            if logical.is_empty() && (line.starts_with('#') || line.starts_with(';')) {
+                continue;
+            }
+            if let Some(continued) = line.strip_suffix('\\') {
+                logical.push_str(continued.trim_end());
+                logical.push(' ');
+                continue;
+            }
+            logical.push_str(line);
+            let line = std::mem::take(&mut logical);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = self.normalize(name.trim());
+                sections
+                    .entry(section.clone())
+                    .or_insert_with(|| Map::new().into());
+                continue;
+            }
+            let (key, val) = line.split_once('=').unwrap_or((line, ""));
+            let (key, val) = (self.normalize(key.trim()), val.trim());
+            let keys = sections
+                .entry(section.clone())
+                .or_insert_with(|| Map::new().into())
+                .as_object_mut()
+                .unwrap();
+            if self.multi.iter().any(|m| self.normalize(m) == key) {
+                let vals = keys
+                    .entry(key.clone())
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                if let Value::Array(vals) = vals {
+                    if val.is_empty() {
+                        vals.clear();
+                    } else {
+                        vals.push(val.into());
+                    }
+                }
+            } else {
+                keys.insert(key.clone(), val.into());
+            }
+            if let Value::Object(keys) = sources
+                .entry(section.clone())
+                .or_insert_with(|| Map::new().into())
+            {
+                keys.insert(key, source.into());
+            }
+        }
+    }
+
+    fn normalize(&self, name: &str) -> String {
+        if self.case_insensitive {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -213,6 +1255,8 @@ mod tests {
             expr: "^(?<key>[^#\\s]+)=(?<val>[^#\\s]+)$".to_string(),
             flags: None,
             map_key_val: true,
+            types: HashMap::new(),
+            regex: Default::default(),
         };
 
         let input = "CONFIG_ARCH_USE_MEMTEST=y\n\
@@ -227,7 +1271,7 @@ mod tests {
                     # CONFIG_RUST_BUILD_ASSERT_ALLOW is not set\n\
                     # end of Kernel hacking";
 
-        let output = w.wrap(input);
+        let output = w.wrap(input).unwrap();
         dbg!(&output);
 
         let mut map = Map::new();
@@ -239,13 +1283,15 @@ mod tests {
 
     #[test]
     fn cmdline_wrap() {
-        let w = CmdLine {};
+        let w = CmdLine {
+            types: HashMap::new(),
+        };
 
         let input = "BOOT_IMAGE=(hd0,gpt2)/vmlinuz-6.13.5-200.fc41.x86_64 \
                      root=UUID=cb60f854-e863-43bd-91bf-f67c66277478 ro \
                      rd.luks.uuid=luks-3cc3f5ad-fd3c-4205-b3b4-1ef14b7c6c50 rhg";
 
-        let output = w.wrap(input);
+        let output = w.wrap(input).unwrap();
         dbg!(&output);
 
         let mut res = Map::new();
@@ -266,4 +1312,538 @@ mod tests {
 
         assert_eq!(output, Value::from(res));
     }
+
+    #[test]
+    fn ini_wrap() {
+        let w = Ini {
+            case_insensitive: false,
+            multi: vec!["Environment".to_string(), "ExecStartPre".to_string()],
+            merge: false,
+        };
+
+        let input = "# Global\n\
+                     gpgcheck=1\n\
+                     [Service]\n\
+                     ; comment\n\
+                     ExecStart=/usr/bin/foo \\\n\
+                     \x20   --bar\n\
+                     Environment=A=1\n\
+                     Environment=B=2\n\
+                     ExecStartPre=/usr/bin/true\n\
+                     ExecStartPre=\n\
+                     Restart=always\n\
+                     Restart=no\n\
+                     [Install]\n\
+                     WantedBy=multi-user.target\n";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "": {"gpgcheck": "1"},
+                "Service": {
+                    "ExecStart": "/usr/bin/foo --bar",
+                    "Environment": ["A=1", "B=2"],
+                    "ExecStartPre": [],
+                    "Restart": "no"
+                },
+                "Install": {"WantedBy": "multi-user.target"}
+            })
+        );
+    }
+
+    #[test]
+    fn ini_wrap_all_merge() {
+        let w = Ini {
+            case_insensitive: true,
+            multi: vec!["KillExcludeUsers".to_string()],
+            merge: true,
+        };
+
+        let inputs = vec![
+            (
+                "/etc/systemd/logind.conf".to_string(),
+                "[Login]\nKillUserProcesses=no\nIdleAction=ignore\nKillExcludeUsers=root\n"
+                    .to_string(),
+            ),
+            (
+                "/etc/systemd/logind.conf.d/10-kill.conf".to_string(),
+                "[login]\nKillUserProcesses=yes\nKillExcludeUsers=\nKillExcludeUsers=gdm\n"
+                    .to_string(),
+            ),
+            (
+                "/etc/systemd/logind.conf.d/20-idle.conf".to_string(),
+                "[Login]\nIdleAction=\nIdleAction=lock\nHandlePowerKey=ignore\n".to_string(),
+            ),
+        ];
+
+        let output = w.wrap_all(&inputs).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!([{
+                "source": "/etc/systemd/logind.conf",
+                "data": {"login": {
+                    "killuserprocesses": "yes",
+                    "idleaction": "lock",
+                    "killexcludeusers": ["gdm"],
+                    "handlepowerkey": "ignore"
+                }},
+                "sources": {"login": {
+                    "killuserprocesses": "/etc/systemd/logind.conf.d/10-kill.conf",
+                    "killexcludeusers": "/etc/systemd/logind.conf.d/10-kill.conf",
+                    "idleaction": "/etc/systemd/logind.conf.d/20-idle.conf",
+                    "handlepowerkey": "/etc/systemd/logind.conf.d/20-idle.conf"
+                }}
+            }])
+        );
+    }
+
+    #[test]
+    fn yaml_wrap() {
+        let w = Yaml {};
+
+        let input = "apiVersion: v1\n\
+                     kind: Pod\n\
+                     spec:\n  hostNetwork: true\n\
+                     ---\n\
+                     kind: Service\n";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!([
+                {"apiVersion": "v1", "kind": "Pod", "spec": {"hostNetwork": true}},
+                {"kind": "Service"}
+            ])
+        );
+        assert!(w.wrap("key: [unclosed").is_err());
+    }
+
+    #[test]
+    fn toml_wrap() {
+        let w = Toml {};
+
+        let input = "[engine]\n\
+                     cgroup_manager = \"systemd\"\n\
+                     num_locks = 2048\n\
+                     updated = 2025-03-22T10:00:00Z\n\
+                     env = [\"A=1\"]\n";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!({"engine": {
+                "cgroup_manager": "systemd",
+                "num_locks": 2048,
+                "updated": "2025-03-22T10:00:00Z",
+                "env": ["A=1"]
+            }})
+        );
+        assert!(w.wrap("key = ").is_err());
+    }
+
+    #[test]
+    fn xml_wrap() {
+        let w = Xml {
+            xpath: None,
+            namespaces: HashMap::new(),
+            compiled: Default::default(),
+        };
+
+        let input = "<?xml version=\"1.0\"?>\n\
+                     <domain type=\"kvm\">\n\
+                       <name>guest</name>\n\
+                       <devices>\n\
+                         <disk type=\"file\"><source file=\"/a.img\"/></disk>\n\
+                         <disk type=\"block\">raw</disk>\n\
+                       </devices>\n\
+                     </domain>";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!({"domain": {
+                "@type": "kvm",
+                "name": "guest",
+                "devices": {"disk": [
+                    {"@type": "file", "source": {"@file": "/a.img"}},
+                    {"@type": "block", "#text": "raw"}
+                ]}
+            }})
+        );
+        assert!(w.wrap("<domain>").is_err());
+    }
+
+    #[test]
+    fn xml_wrap_xpath() {
+        let mut namespaces = HashMap::new();
+        namespaces.insert(
+            "oval".to_string(),
+            "http://oval.mitre.org/XMLSchema/oval-definitions-5".to_string(),
+        );
+        let w = Xml {
+            xpath: Some("//oval:definition/oval:metadata/oval:affected/@family".to_string()),
+            namespaces,
+            compiled: Default::default(),
+        };
+
+        let input = std::fs::read_to_string("content/test_lockdown.xml").unwrap();
+        let output = w.wrap(&input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(output, serde_json::json!(["unix"]));
+
+        let w: Xml = serde_json::from_str(r#"{"xpath": "//oval:definition["}"#).unwrap();
+        assert!(matches!(w.compile(), Err(Error::Wrapper(_))));
+    }
+
+    #[test]
+    fn columns_wrap_passwd() {
+        let mut types = HashMap::new();
+        types.insert("uid".to_string(), Coercion::Int);
+        let w = Columns {
+            delimiter: Some(':'),
+            columns: ["name", "password", "uid", "gid", "gecos", "home", "shell"]
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+            header: false,
+            comment: Some("#".to_string()),
+            quote: None,
+            types,
+        };
+
+        let input = "# users\nroot:x:0:0::/root:/bin/bash\n";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!([{
+                "name": "root", "password": "x", "uid": 0, "gid": "0",
+                "gecos": "", "home": "/root", "shell": "/bin/bash"
+            }])
+        );
+    }
+
+    #[test]
+    fn columns_wrap_csv_header() {
+        let mut types = HashMap::new();
+        types.insert("enabled".to_string(), Coercion::Bool);
+        let w = Columns {
+            delimiter: Some(','),
+            columns: vec![],
+            header: true,
+            comment: None,
+            quote: Some('"'),
+            types,
+        };
+
+        let input = "name,enabled,note\n\
+                     sshd,yes,\"listens on 22, \"\"public\"\"\"\n";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!([{
+                "name": "sshd", "enabled": true, "note": "listens on 22, \"public\""
+            }])
+        );
+        assert!(w.wrap("name,enabled\nsshd,maybe\n").is_err());
+    }
+
+    #[test]
+    fn columns_wrap_ps_tail() {
+        let w = Columns {
+            delimiter: None,
+            columns: vec!["pid".to_string(), "cmd".to_string()],
+            header: false,
+            comment: None,
+            quote: None,
+            types: HashMap::new(),
+        };
+
+        let output = w.wrap("  812   /sbin/auditd -n\n  2\n").unwrap();
+
+        assert_eq!(
+            output,
+            serde_json::json!([
+                {"pid": "812", "cmd": "/sbin/auditd -n"},
+                {"pid": "2", "cmd": null}
+            ])
+        );
+    }
+
+    #[test]
+    fn env_file_wrap() {
+        let w = EnvFile {};
+
+        let input = "# /etc/default/grub\n\
+                     GRUB_TIMEOUT=5\n\
+                     GRUB_CMDLINE_LINUX=\"rd.luks.uuid=luks-3cc3 rhgb quiet\" # boot args\n\
+                     export GRUB_DISTRIBUTOR='Fedora Linux'\n\
+                     ESCAPED=a\\ b\"c\\\"d\"\n\
+                     MULTI=\"one \\\n\
+                     two\"\n\
+                     EMPTY=\n\
+                     not an assignment\n\
+                     SUB=$HOME/bin\n";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "GRUB_TIMEOUT": "5",
+                "GRUB_CMDLINE_LINUX": "rd.luks.uuid=luks-3cc3 rhgb quiet",
+                "GRUB_DISTRIBUTOR": "Fedora Linux",
+                "ESCAPED": "a bc\"d",
+                "MULTI": "one two",
+                "EMPTY": "",
+                "SUB": "$HOME/bin"
+            })
+        );
+    }
+
+    #[test]
+    fn keyword_wrap() {
+        let w = Keyword {
+            multi: vec!["AllowUsers".to_string()],
+            last_match: false,
+            conditional_keywords: default_conditional_keywords(),
+            include_keywords: default_include_keywords(),
+            include_base: None,
+        };
+
+        let input = "# sshd_config\n\
+                     Include /etc/ssh/sshd_config.d/*.conf\n\
+                     PermitRootLogin no\n\
+                     permitrootlogin yes\n\
+                     AllowUsers alice bob\n\
+                     AllowUsers carol\n\
+                     X11Forwarding=no\n\
+                     Match User backup Address 10.0.0.*\n\
+                     \x20   PermitRootLogin prohibit-password\n";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "global": {
+                    "permitrootlogin": "no",
+                    "allowusers": ["alice bob", "carol"],
+                    "x11forwarding": "no"
+                },
+                "conditional": [{
+                    "keyword": "match",
+                    "criteria": "User backup Address 10.0.0.*",
+                    "settings": {"permitrootlogin": "prohibit-password"}
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn pipeline_wrap() {
+        let p: Pipeline = serde_json::from_str(
+            r#"[
+                {"raw-lines": {}},
+                {"json": {}}
+            ]"#,
+        )
+        .unwrap();
+
+        let inputs = vec![(
+            "journal".to_string(),
+            "{\"unit\": \"sshd\"}\n{\"unit\": \"auditd\"}\n".to_string(),
+        )];
+
+        let output = p.wrap_all_with_runner(&mut Runner::new(), &inputs).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!([{
+                "source": "journal",
+                "data": [{"unit": "sshd"}, {"unit": "auditd"}]
+            }])
+        );
+    }
+
+    #[test]
+    fn pipeline_wrap_single() {
+        let p: Pipeline = serde_json::from_str(r#"{"cmd-line": {}}"#).unwrap();
+        assert_eq!(
+            p,
+            Pipeline::Single(Wrapper::CmdLine(CmdLine {
+                types: HashMap::new()
+            }))
+        );
+        assert!(p.wrap_value(&Value::from(1)).is_err());
+    }
+
+    #[test]
+    fn pipeline_errors() {
+        let e = serde_json::from_str::<Pipeline>("[]").unwrap_err();
+        assert!(e.to_string().contains("at least one stage"));
+
+        let e = serde_json::from_str::<Pipeline>(r#"[{"raw-lines": {}}, {"jsn": {}}]"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.starts_with("Wrapper stage 2: unknown variant `jsn`"),
+            "{}",
+            e
+        );
+
+        let p: Pipeline = serde_json::from_str(r#"[{"yaml": {}}, {"ini": {}}]"#).unwrap();
+        assert_eq!(
+            p.wrap("a: 1\n"),
+            Err(Error::Wrapper(
+                "Stage 2 (ini): Unable to wrap an object, text or a list of texts is expected! \
+                 Only json takes structured values from a previous stage."
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn regexp_wrap_groups() {
+        let regexp = |expr: &str| -> Regexp {
+            serde_json::from_value(serde_json::json!({ "expr": expr })).unwrap()
+        };
+
+        let w = regexp("^(\\w+)(?: (\\d+))?$");
+        assert_eq!(
+            w.wrap("umask 027\numask\n").unwrap(),
+            serde_json::json!([["umask", "027"], ["umask", null]])
+        );
+
+        let w = regexp("^(?<key>\\w+) (\\d+)$");
+        assert!(matches!(w.compile(), Err(Error::Wrapper(_))));
+        assert!(matches!(w.wrap("umask 027"), Err(Error::Wrapper(_))));
+    }
+
+    #[test]
+    fn regexp_wrap_types() {
+        let w: Regexp = serde_json::from_str(
+            r#"{
+                "expr": "^(?<key>[^#\\s]+)=\"*(?<val>[^#\"\\n]*)\"*$",
+                "map_key_val": true,
+                "types": {
+                    "CONFIG_MODULES": "bool",
+                    "CONFIG_NR_CPUS": "int",
+                    "CONFIG_LSM": {"list": ","},
+                    "UMASK": "octal"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let input = "CONFIG_MODULES=y\n\
+                     CONFIG_NR_CPUS=8192\n\
+                     CONFIG_LSM=\"lockdown,yama,integrity\"\n\
+                     UMASK=0022\n\
+                     CONFIG_CC_VERSION_TEXT=\"gcc (GCC) 14.2.1\"\n";
+
+        let output = w.wrap(input).unwrap();
+        dbg!(&output);
+
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "CONFIG_MODULES": true,
+                "CONFIG_NR_CPUS": 8192,
+                "CONFIG_LSM": ["lockdown", "yama", "integrity"],
+                "UMASK": 18,
+                "CONFIG_CC_VERSION_TEXT": "gcc (GCC) 14.2.1"
+            })
+        );
+    }
+
+    #[test]
+    fn cmdline_wrap_types() {
+        let mut types = HashMap::new();
+        types.insert("audit".to_string(), Coercion::Bool);
+        types.insert("audit_backlog_limit".to_string(), Coercion::Int);
+        let w = CmdLine { types };
+
+        let output = w.wrap("ro audit=1 audit_backlog_limit=8192").unwrap();
+
+        assert_eq!(
+            output,
+            serde_json::json!({"ro": true, "audit": true, "audit_backlog_limit": 8192})
+        );
+        assert!(w.wrap("audit=maybe").is_err());
+    }
+
+    #[test]
+    fn json_wrap_select() {
+        let input = r#"[{"Name": "web", "State": {"Status": "running"}, "HostConfig": {"Privileged": true}}]"#;
+
+        let w: Json = serde_json::from_str(r#"{"select": {"pointer": "/0/HostConfig"}}"#).unwrap();
+        assert_eq!(
+            w.wrap(input).unwrap(),
+            serde_json::json!({"Privileged": true})
+        );
+
+        let w: Json = serde_json::from_str(
+            r#"{"select": {"jmespath": "[].{name: Name, state: State.Status}"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            w.wrap(input).unwrap(),
+            serde_json::json!([{"name": "web", "state": "running"}])
+        );
+
+        let w: Json = serde_json::from_str(r#"{"select": {"jmespath": "[?"}}"#).unwrap();
+        assert!(w.compile().is_err());
+        assert!(w.wrap(input).is_err());
+    }
+
+    #[test]
+    fn pipeline_wrap_select_structured() {
+        let p: Pipeline =
+            serde_json::from_str(r#"[{"yaml": {}}, {"json": {"select": {"pointer": "/0/spec"}}}]"#)
+                .unwrap();
+
+        let input = "kind: Pod\nspec:\n  hostNetwork: true\n";
+
+        assert_eq!(
+            p.wrap(input).unwrap(),
+            serde_json::json!({"hostNetwork": true})
+        );
+    }
+
+    #[test]
+    fn json_seq_wrap_select() {
+        let w = JsonSeq {
+            select: Some(Selection::Pointer("/name".to_string())),
+            jmespath: Default::default(),
+        };
+
+        let input = "\x1e{\"name\": \"eth0\"}\n\x1e{\"name\": \"lo\"}\n";
+
+        assert_eq!(w.wrap(input).unwrap(), serde_json::json!(["eth0", "lo"]));
+        assert!(matches!(
+            w.wrap("\x1e{\"name\": \"eth0\"}\n\x1e{\"name\": \n"),
+            Err(Error::Wrapper(_))
+        ));
+    }
 }
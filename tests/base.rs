@@ -1,8 +1,22 @@
 use prospector::block::*;
+use prospector::report::*;
 use prospector::runner::*;
+use prospector::trace::*;
 use semver::{Version, VersionReq};
 use serde_json::Map;
 
+/// Runner replaying `content/<fixture>`.
+fn fixture_runner(fixture: &str) -> Runner {
+    Runner::new_with_target(&Some(format!("fixture:///content/{}", fixture)))
+}
+
+/// Loads `content/<test>` with a runner replaying `content/<fixture>`.
+fn load_with_fixture(test: &str, fixture: &str) -> (Runner, Test) {
+    let json_s = std::fs::read_to_string(format!("content/{}", test)).unwrap();
+    let t = Test::create_from_json(json_s).expect("Can't create test from JSON");
+    (fixture_runner(fixture), t)
+}
+
 #[test]
 fn block_create_from_json_and_run_probe_file_raw_lines() {
     let json_s = r#"{
@@ -20,7 +34,7 @@ fn block_create_from_json_and_run_probe_file_raw_lines() {
 
     let mut r = Runner::new();
     let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
-    let s = b.execute(&mut r, &Map::new());
+    let s = b.execute(&mut r, &Map::new()).unwrap();
     println!("{:#?}", s);
 }
 
@@ -44,10 +58,90 @@ fn block_create_from_json_and_run_probe_file_regexp() {
 
     let mut r = Runner::new();
     let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
-    let s = b.execute(&mut r, &Map::new());
+    let s = b.execute(&mut r, &Map::new()).unwrap();
     println!("{:#?}", s);
 }
 
+#[test]
+fn block_create_from_json_and_run_probe_mounts() {
+    let json_s = r#"{
+            "id": "block_id",
+            "probe": {
+                "mounts": {
+                    "fstab": true
+                }
+            }
+        }"#
+    .to_string();
+
+    let mut r = fixture_runner("test_mounts.fixture.json");
+    let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
+    let s = b.execute(&mut r, &Map::new()).unwrap();
+
+    assert_eq!(s[0]["source"], "/proc/self/mountinfo");
+    let mounts = s[0]["data"].as_array().unwrap();
+    assert_eq!(mounts.len(), 3);
+    assert_eq!(
+        mounts[1],
+        serde_json::json!({
+            "mount_id": 35, "parent_id": 22, "device": "0:31", "root": "/",
+            "mount_point": "/tmp", "options": ["nodev", "noexec", "nosuid", "rw"],
+            "propagation": ["shared:14"], "fs_type": "tmpfs", "source": "tmpfs",
+            "super_options": ["rw", "size=4096k"]
+        })
+    );
+    assert_eq!(s[1]["source"], "/etc/fstab");
+    assert_eq!(
+        s[1]["data"][2],
+        serde_json::json!({
+            "source": "/dev/mapper/home", "mount_point": "/home", "fs_type": "xfs",
+            "options": ["defaults"], "freq": 0, "passno": 0
+        })
+    );
+}
+
+#[test]
+fn test_run_probe_mounts_with_fixture() {
+    let (mut r, t) = load_with_fixture("test_mounts.json", "test_mounts.fixture.json");
+    assert_eq!(t.execute(&mut r).unwrap().result, true);
+}
+
+#[test]
+fn block_create_from_json_and_run_probe_file_columns() {
+    let json_s = r##"{
+            "id": "block_id",
+            "probe": {
+                "file": {
+                    "paths": ["/etc/fstab"]
+                }
+            },
+            "wrapper": {
+                "columns": {
+                    "columns": ["fs_spec", "fs_file", "fs_vfstype", "fs_mntops", "fs_freq", "fs_passno"],
+                    "comment": "#",
+                    "types": {"fs_freq": "int", "fs_passno": "int"}
+                }
+            }
+        }"##
+    .to_string();
+
+    let mut r = fixture_runner("test_mounts.fixture.json");
+    let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
+    let s = b.execute(&mut r, &Map::new()).unwrap();
+
+    assert_eq!(
+        s,
+        serde_json::json!([{"source": "/etc/fstab", "data": [
+            {"fs_spec": "/dev/mapper/root", "fs_file": "/", "fs_vfstype": "xfs",
+             "fs_mntops": "defaults", "fs_freq": 0, "fs_passno": 0},
+            {"fs_spec": "tmpfs", "fs_file": "/tmp", "fs_vfstype": "tmpfs",
+             "fs_mntops": "nosuid,nodev,noexec", "fs_freq": 0, "fs_passno": 0},
+            {"fs_spec": "/dev/mapper/home", "fs_file": "/home", "fs_vfstype": "xfs",
+             "fs_mntops": "defaults", "fs_freq": null, "fs_passno": null}
+        ]}])
+    );
+}
+
 #[test]
 fn block_create_from_json_and_run_probe_process() {
     let json_s = r#"{
@@ -63,7 +157,7 @@ fn block_create_from_json_and_run_probe_process() {
 
     let mut r = Runner::new();
     let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
-    let s = b.execute(&mut r, &Map::new());
+    let s = b.execute(&mut r, &Map::new()).unwrap();
     println!("{:#?}", s);
 }
 
@@ -82,7 +176,7 @@ fn block_create_from_json_and_exec_filter_cel() {
 
     let mut r = Runner::new();
     let b: Block = Block::create_from_json(json_s).expect("Can't create block from JSON");
-    let s = b.execute(&mut r, &Map::new());
+    let s = b.execute(&mut r, &Map::new()).unwrap();
     println!("{:#?}", s);
 }
 
@@ -93,6 +187,26 @@ fn runner_unglob() {
     println!("{:#?}", result);
 }
 
+#[test]
+fn runner_unglob_quoted() {
+    let dir = std::env::temp_dir().join(format!("prospector-unglob-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("it's.conf");
+    std::fs::write(&path, "").unwrap();
+    let dir = dir.to_str().unwrap();
+
+    let mut r = Runner::new();
+    assert_eq!(
+        r.unglob_path(&format!("{}/it's*", dir)),
+        vec![path.to_str().unwrap().to_string()]
+    );
+    // The quote doesn't end the pattern, the rest is not run as a command.
+    assert!(r
+        .unglob_path(&format!("{}/'; echo injected; '", dir))
+        .is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
 #[test]
 fn semver() {
     let req = VersionReq::parse("0,4,>200").expect("Req!");
@@ -101,3 +215,264 @@ fn semver() {
     dbg!(&version);
     // assert!(req.matches(&version));
 }
+
+#[test]
+fn test_run_probe_processes_with_fixture() {
+    let (mut r, t) = load_with_fixture("test_processes.json", "test_processes.fixture.json");
+    let mut trace = Trace::new("auditd_running");
+    assert_eq!(
+        t.execute_with_trace(&mut r, &mut trace).unwrap().result,
+        true
+    );
+    // Only auditd matches the comm and uid, systemd and sshd are skipped after their status.
+    assert_eq!(
+        trace.blocks[0].result,
+        Some(serde_json::json!([{"source": "/proc", "data": [{
+            "pid": 812, "ppid": 1, "uid": 0, "euid": 0, "gid": 0, "egid": 0,
+            "comm": "auditd", "exe": "/usr/sbin/auditd", "cmdline": ["/sbin/auditd"],
+            "cgroup": ["0::/system.slice/auditd.service"]
+        }]}]))
+    );
+}
+
+#[test]
+fn test_run_probe_sockets_with_fixture() {
+    let (mut r, t) = load_with_fixture("test_sockets.json", "test_sockets.fixture.json");
+    let mut trace = Trace::new("no_telnet_listener");
+    assert_eq!(
+        t.execute_with_trace(&mut r, &mut trace).unwrap().result,
+        true
+    );
+    // Only the listening sockets are kept, with the pids holding them.
+    assert_eq!(
+        trace.blocks[0].result,
+        Some(serde_json::json!([
+            {"source": "/proc/net/tcp", "data": [{
+                "protocol": "tcp", "local_address": "0.0.0.0", "local_port": 22,
+                "remote_address": "0.0.0.0", "remote_port": 0, "state": "LISTEN",
+                "listening": true, "inode": 21035, "uid": 0, "pids": [1042]
+            }]},
+            {"source": "/proc/net/tcp6", "data": [{
+                "protocol": "tcp6", "local_address": "::", "local_port": 22,
+                "remote_address": "::", "remote_port": 0, "state": "LISTEN",
+                "listening": true, "inode": 21037, "uid": 0, "pids": [1042]
+            }]}
+        ]))
+    );
+}
+
+#[test]
+fn test_not_applicable_with_fixture() {
+    let (mut r, t) = load_with_fixture("test_platform.json", "test_platform.fixture.debian.json");
+    let outcome = t.execute(&mut r).unwrap();
+    assert!(!outcome.applicable);
+    assert_eq!(outcome.result, serde_json::Value::Null);
+}
+
+#[test]
+fn test_applicable_with_fixture() {
+    let (mut r, t) = load_with_fixture(
+        "test_platform.json",
+        "test_platform.fixture.fedora.false.json",
+    );
+    let mut report = Report::new(&None, vec![]);
+    let mut trace = Trace::new("lockdown_fedora");
+    let test_report = report.add_test(Entity::get_id(&t), || {
+        t.execute_with_trace(&mut r, &mut trace)
+    });
+    assert_eq!(test_report.verdict, Verdict::Fail);
+    assert_eq!(test_report.result, false);
+    let result = trace.blocks.iter().find(|b| b.id == "result").unwrap();
+    assert_eq!(result.result, Some(false.into()));
+}
+
+#[test]
+fn test_platform_usr_lib_os_release_with_fixture() {
+    let (mut r, t) = load_with_fixture(
+        "test_platform.json",
+        "test_platform.fixture.usr_lib.false.json",
+    );
+    let mut trace = Trace::new("lockdown_fedora");
+    let outcome = t.execute_with_trace(&mut r, &mut trace).unwrap();
+    assert!(outcome.applicable);
+    assert_eq!(outcome.result, false);
+    let platform = trace.blocks.iter().find(|b| b.id == "platform").unwrap();
+    let platform = platform.result.as_ref().unwrap();
+    assert_eq!(platform[0]["source"], "/usr/lib/os-release");
+    assert_eq!(platform[0]["data"]["id"], "fedora");
+    assert_eq!(platform[0]["data"]["container"], "podman");
+}
+
+#[test]
+fn test_keyword_include_with_fixture() {
+    let (mut r, t) = load_with_fixture("test_sshd.json", "test_sshd.fixture.json");
+    let outcome = t.execute(&mut r).unwrap();
+    assert_eq!(outcome.result, true);
+    assert_eq!(
+        serde_json::Value::from(outcome.evidence),
+        serde_json::json!({
+            "result": [],
+            "sshd_config/0/source": "/etc/ssh/sshd_config",
+            "sshd_config/0/data/global/permitrootlogin": "no"
+        })
+    );
+}
+
+#[test]
+fn test_keyword_include_match_with_fixture() {
+    let (mut r, t) = load_with_fixture(
+        "test_sshd.json",
+        "test_sshd.fixture.include_match.false.json",
+    );
+    let outcome = t.execute(&mut r).unwrap();
+    assert_eq!(outcome.result, false);
+    // The included Match section ends with its file, the next line is global again.
+    assert_eq!(
+        outcome.evidence["sshd_config/0/data/global/permitrootlogin"],
+        "no"
+    );
+    assert_eq!(
+        outcome.evidence["result"],
+        serde_json::json!([{
+            "keyword": "match",
+            "criteria": "User backup",
+            "settings": {"permitrootlogin": "yes"}
+        }])
+    );
+}
+
+#[test]
+fn test_trace_with_fixture() {
+    let (mut r, t) = load_with_fixture("test_sshd.json", "test_sshd.fixture.json");
+
+    let mut trace = Trace::new("sshd_permit_root_login");
+    assert_eq!(
+        t.execute_with_trace(&mut r, &mut trace).unwrap().result,
+        true
+    );
+    let ids: Vec<&str> = trace.blocks.iter().map(|b| b.id.as_str()).collect();
+    assert_eq!(ids, vec!["sshd_config", "result"]);
+    let probe = &trace.blocks[0];
+    assert_eq!(probe.commands.len(), 3);
+    assert!(probe.commands.iter().all(|c| c.cache == Cache::Miss));
+    assert_eq!(probe.raw[0].source, "/etc/ssh/sshd_config");
+    assert!(probe.inputs.is_none());
+    let filter = &trace.blocks[1];
+    assert!(filter.commands.is_empty());
+    assert!(filter.inputs.as_ref().unwrap().contains_key("sshd_config"));
+    assert_eq!(filter.result.as_ref().unwrap()["result"], true);
+
+    // The runner's cache is shared by the tests of a run.
+    let mut trace = Trace::new("sshd_permit_root_login");
+    t.execute_with_trace(&mut r, &mut trace).unwrap();
+    assert!(trace.blocks[0]
+        .commands
+        .iter()
+        .all(|c| c.cache == Cache::Hit));
+}
+
+#[test]
+fn test_report_matches_schema() {
+    let schema: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("schema/report.schema.json").unwrap())
+            .unwrap();
+    let validator = jsonschema::validator_for(&schema).expect("Invalid schema!");
+
+    let target = Some("fixture:///content/test_platform.fixture.debian.json".to_string());
+    let mut r = Runner::new_with_target(&target);
+    let json_s = std::fs::read_to_string("content/test_platform.json").unwrap();
+    let mut report = Report::new(
+        &target,
+        vec![Content::new("content/test_platform.json", &json_s)],
+    );
+    // The document printed when the tests can't be loaded.
+    let unfinished = serde_json::to_value(&report).unwrap();
+    assert!(validator.is_valid(&unfinished));
+    assert_eq!(unfinished["run"]["finished"], serde_json::Value::Null);
+
+    let t = Test::create_from_json(json_s).expect("Can't create test from JSON");
+    report.add_test(Entity::get_id(&t), || t.execute(&mut r));
+    report.add_test("broken", || {
+        Err(prospector::error::Error::Filter("boom".to_string()))
+    });
+    report.finish();
+
+    let document = serde_json::to_value(&report).unwrap();
+    if let Some(e) = validator.iter_errors(&document).next() {
+        panic!("{} at {}", e, e.instance_path);
+    }
+    assert_eq!(report.tests[0].verdict, Verdict::NotApplicable);
+    assert_eq!(report.tests[1].verdict, Verdict::Error);
+    assert_eq!(document["tests"][1]["errors"][0], "Filter error: boom");
+}
+
+/// Runs the tests against a fixture and compares the JUnit report with `tests/golden/<name>`,
+/// timings are zeroed to keep the report stable. `UPDATE_GOLDEN=1` rewrites the golden file.
+fn assert_junit_golden(name: &str, fixture: &str, inputs: &[&str], tracing: bool) -> Report {
+    let target = Some(format!("fixture:///content/{}", fixture));
+    let mut r = Runner::new_with_target(&target);
+    let mut report = Report::new(&target, vec![]);
+    let tests: Vec<Test> = inputs
+        .iter()
+        .map(|input| {
+            Test::create_from_json(std::fs::read_to_string(input).unwrap())
+                .expect("Can't create test from JSON")
+        })
+        .collect();
+    prospector::run_tests(&mut r, &tests, &mut report, tracing);
+    report.add_test("broken", || {
+        Err(prospector::error::Error::Filter("boom <&>".to_string()))
+    });
+
+    report.run.started = "2025-01-01T00:00:00Z".to_string();
+    for test in &mut report.tests {
+        test.wall_time_ms = 0.0;
+        if let Some(trace) = &mut test.trace {
+            trace.blocks.iter_mut().for_each(|b| b.wall_time_ms = 0.0);
+        }
+    }
+    let xml = report.render(Format::Junit);
+    let path = format!("tests/golden/{}", name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &xml).unwrap();
+    }
+    assert_eq!(xml, std::fs::read_to_string(&path).unwrap());
+    report
+}
+
+#[test]
+fn test_junit_report_with_fixture() {
+    let report = assert_junit_golden(
+        "sshd.junit.xml",
+        "test_sshd.fixture.json",
+        &["content/test_sshd.json"],
+        true,
+    );
+    assert_eq!(report.tests[0].verdict, Verdict::Pass);
+    assert_eq!(report.tests[1].verdict, Verdict::Error);
+
+    let report = assert_junit_golden(
+        "lockdown.junit.xml",
+        "test_lockdown.fixture.not_set.false.json",
+        &["content/test_lockdown.json"],
+        false,
+    );
+    assert_eq!(report.tests[0].verdict, Verdict::Fail);
+
+    let report = assert_junit_golden(
+        "sshd.match_yes.junit.xml",
+        "test_sshd.fixture.match_yes.false.json",
+        &["content/test_sshd.json"],
+        false,
+    );
+    assert_eq!(report.tests[0].verdict, Verdict::Fail);
+    assert!(!report.tests[0].evidence.is_empty());
+
+    let report = assert_junit_golden(
+        "platform.junit.xml",
+        "test_platform.fixture.debian.json",
+        &["content/test_platform.json"],
+        false,
+    );
+    assert_eq!(report.tests[0].verdict, Verdict::NotApplicable);
+}